@@ -1,9 +1,24 @@
+#[cfg(not(target_arch = "wasm32"))]
+use crate::recorder::{self, RecordTap, TapProducer};
 use rtrb::{Consumer, Producer, RingBuffer};
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use web_time::Instant;
 
 pub const BUFFER_SIZE: usize = 4096;
 
+//2 seconds of audio at 48 kHz
+pub const RING_CAPACITY: usize = 96000;
+
 pub struct AudioBridge {
     pub consumer: Consumer<f32>,
+    ///Shared with the producer end, to watch the bridge from the ui
+    pub stats: BridgeStats,
+    ///Where a Recorder reads a copy of the samples from
+    #[cfg(not(target_arch = "wasm32"))]
+    pub tap: RecordTap,
     // pub sample_rate: f32,
 }
 
@@ -11,8 +26,189 @@ pub struct AudioBridge {
 //or in our audio callback with cpal. the consumer end will be used by the DSP to compute and
 //provide final data to Ui
 impl AudioBridge {
-    pub fn new() -> (Self, Producer<f32>) {
-        let (producer, consumer) = RingBuffer::new(96000);
-        (Self { consumer }, producer)
+    pub fn new() -> (Self, BridgeProducer) {
+        let (producer, consumer) = RingBuffer::new(RING_CAPACITY);
+        let stats = BridgeStats::new(RING_CAPACITY);
+        #[cfg(not(target_arch = "wasm32"))]
+        let (tap_producer, tap) = recorder::tap(RING_CAPACITY);
+        (
+            Self {
+                consumer,
+                stats: stats.clone(),
+                #[cfg(not(target_arch = "wasm32"))]
+                tap,
+            },
+            BridgeProducer {
+                producer,
+                stats,
+                pushed: 0,
+                dropped: 0,
+                #[cfg(not(target_arch = "wasm32"))]
+                tap: tap_producer,
+            },
+        )
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    pushed: AtomicU64,
+    dropped: AtomicU64,
+    callbacks: AtomicU64,
+    last_callback_ns: AtomicU64,
+    max_callback_ns: AtomicU64,
+    total_callback_ns: AtomicU64,
+    fill: AtomicUsize,
+}
+
+///Counters updated by the audio side with atomics only, so reading them never blocks the callback
+#[derive(Clone)]
+pub struct BridgeStats {
+    counters: Arc<Counters>,
+    capacity: usize,
+}
+
+///What BridgeStats held when we read it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StatsSnapshot {
+    pub pushed: u64,
+    ///Samples lost because the ring buffer was full
+    pub dropped: u64,
+    pub callbacks: u64,
+    pub last_callback: Duration,
+    pub max_callback: Duration,
+    pub mean_callback: Duration,
+    ///Samples waiting in the ring buffer after the last callback
+    pub fill: usize,
+    pub capacity: usize,
+}
+
+impl BridgeStats {
+    fn new(capacity: usize) -> Self {
+        Self {
+            counters: Arc::new(Counters::default()),
+            capacity,
+        }
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let c = &self.counters;
+        let callbacks = c.callbacks.load(Ordering::Relaxed);
+        let total = c.total_callback_ns.load(Ordering::Relaxed);
+        StatsSnapshot {
+            pushed: c.pushed.load(Ordering::Relaxed),
+            dropped: c.dropped.load(Ordering::Relaxed),
+            callbacks,
+            last_callback: Duration::from_nanos(c.last_callback_ns.load(Ordering::Relaxed)),
+            max_callback: Duration::from_nanos(c.max_callback_ns.load(Ordering::Relaxed)),
+            mean_callback: Duration::from_nanos(total.checked_div(callbacks).unwrap_or(0)),
+            fill: c.fill.load(Ordering::Relaxed),
+            capacity: self.capacity,
+        }
+    }
+}
+
+impl StatsSnapshot {
+    pub fn fill_ratio(&self) -> f32 {
+        self.fill as f32 / self.capacity.max(1) as f32
+    }
+}
+
+impl fmt::Display for StatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "callbacks {} | pushed {} | dropped {} | fill {}/{} ({:.0}%) | callback last {:?} mean {:?} max {:?}",
+            self.callbacks,
+            self.pushed,
+            self.dropped,
+            self.fill,
+            self.capacity,
+            self.fill_ratio() * 100.0,
+            self.last_callback,
+            self.mean_callback,
+            self.max_callback
+        )
+    }
+}
+
+///The producer end every backend writes to : a plain rtrb Producer that also counts what it does
+pub struct BridgeProducer {
+    producer: Producer<f32>,
+    stats: BridgeStats,
+    //counted by push sample by sample, and published once at the end of the callback
+    pushed: u64,
+    dropped: u64,
+    #[cfg(not(target_arch = "wasm32"))]
+    tap: TapProducer,
+}
+
+impl BridgeProducer {
+    ///Pushes one sample, or counts it as dropped when the ring buffer is full. The counts reach
+    ///the stats when the callback wrapping the pushes ends
+    pub fn push(&mut self, sample: f32) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.tap.push(sample);
+        match self.producer.push(sample) {
+            Ok(()) => {
+                self.pushed += 1;
+                true
+            }
+            Err(_) => {
+                self.dropped += 1;
+                false
+            }
+        }
+    }
+
+    ///Pushes as many samples as fit, the rest is counted as dropped. Returns how many were written
+    pub fn push_slice(&mut self, samples: &[f32]) -> usize {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.tap.push_slice(samples);
+        let n = samples.len().min(self.producer.slots());
+        if n > 0
+            && let Ok(chunk) = self.producer.write_chunk_uninit(n)
+        {
+            chunk.fill_from_iter(samples[..n].iter().copied());
+        }
+        let c = &self.stats.counters;
+        c.pushed.fetch_add(n as u64, Ordering::Relaxed);
+        c.dropped
+            .fetch_add((samples.len() - n) as u64, Ordering::Relaxed);
+        n
+    }
+
+    ///Free room in the ring buffer
+    pub fn slots(&self) -> usize {
+        self.producer.slots()
+    }
+
+    ///Wraps one audio callback : counts it, times it and records the fill level after it
+    pub fn callback<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let start = Instant::now();
+        let result = f(self);
+        let elapsed = start.elapsed().as_nanos() as u64;
+        let c = &self.stats.counters;
+        if self.pushed > 0 {
+            c.pushed
+                .fetch_add(std::mem::take(&mut self.pushed), Ordering::Relaxed);
+        }
+        if self.dropped > 0 {
+            c.dropped
+                .fetch_add(std::mem::take(&mut self.dropped), Ordering::Relaxed);
+        }
+        c.callbacks.fetch_add(1, Ordering::Relaxed);
+        c.last_callback_ns.store(elapsed, Ordering::Relaxed);
+        c.max_callback_ns.fetch_max(elapsed, Ordering::Relaxed);
+        c.total_callback_ns.fetch_add(elapsed, Ordering::Relaxed);
+        c.fill.store(
+            self.stats.capacity - self.producer.slots(),
+            Ordering::Relaxed,
+        );
+        result
+    }
+
+    pub fn stats(&self) -> &BridgeStats {
+        &self.stats
     }
 }
@@ -0,0 +1,45 @@
+use super::paced::{PacedBackend, SampleSource};
+use crate::audio_bridge::BridgeProducer;
+use crate::wav;
+use std::path::Path;
+use std::time::Duration;
+
+///The decoded samples of a file, and how far we played them
+pub struct FileSource {
+    samples: Vec<f32>,
+    position: usize,
+}
+
+impl SampleSource for FileSource {
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        let n = out.len().min(self.samples.len() - self.position);
+        out[..n].copy_from_slice(&self.samples[self.position..self.position + n]);
+        self.position += n;
+        n
+    }
+}
+
+///Plays a WAV file into the ringbuf, in real time or as fast as possible
+pub type FileAudioBackend = PacedBackend<FileSource>;
+
+impl FileAudioBackend {
+    pub fn new(producer: BridgeProducer, path: &Path, realtime: bool) -> Result<Self, String> {
+        let data = wav::read_wav(path)?;
+        let source = FileSource {
+            samples: data.samples,
+            position: 0,
+        };
+        Ok(Self::from_source(
+            producer,
+            source,
+            data.sample_rate,
+            realtime,
+        ))
+    }
+
+    ///Length of the file, None while it is playing
+    pub fn duration(&self) -> Option<Duration> {
+        self.source()
+            .map(|s| Duration::from_secs_f32(s.samples.len() as f32 / self.sample_rate))
+    }
+}
@@ -0,0 +1,26 @@
+use super::paced::{PacedBackend, SampleSource};
+use crate::audio_bridge::BridgeProducer;
+use crate::generator::{Signal, SignalGenerator};
+
+impl SampleSource for SignalGenerator {
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        SignalGenerator::fill(self, out);
+        out.len()
+    }
+}
+
+///Feeds a synthetic signal into the ringbuf, so the whole pipeline runs without a sound card
+pub type GeneratorBackend = PacedBackend<SignalGenerator>;
+
+impl GeneratorBackend {
+    pub fn new(
+        producer: BridgeProducer,
+        signal: Signal,
+        amplitude: f32,
+        sample_rate: f32,
+        realtime: bool,
+    ) -> Self {
+        let generator = SignalGenerator::new(signal, amplitude, sample_rate);
+        Self::from_source(producer, generator, sample_rate, realtime)
+    }
+}
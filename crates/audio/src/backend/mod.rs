@@ -3,8 +3,18 @@ pub mod wasm;
 #[cfg(target_arch = "wasm32")]
 pub use wasm::WasmAudioBackend;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::audio_bridge::BridgeProducer;
+#[cfg(not(target_arch = "wasm32"))]
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+pub mod file;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod generator;
 #[cfg(not(target_arch = "wasm32"))]
 pub mod native;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod paced;
 
 ///This trait allows us to abstract the backend.
 ///using start and stop will call either the wasm or native one without duplicating code
@@ -12,4 +22,70 @@ pub trait AudioBackend {
     fn start(&mut self) -> Result<(), String>;
     fn stop(&mut self);
     fn sample_rate(&self) -> f32;
+    ///A live input never ends, but a file does
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+///Where a native frontend takes its samples from
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InputSource {
+    Device {
+        device: Option<native::DeviceSelector>,
+        channel: native::ChannelSelection,
+    },
+    File {
+        path: std::path::PathBuf,
+        realtime: bool,
+    },
+    Generator {
+        signal: crate::generator::Signal,
+        amplitude: f32,
+        sample_rate: f32,
+        realtime: bool,
+    },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for InputSource {
+    fn default() -> Self {
+        InputSource::Device {
+            device: None,
+            channel: native::ChannelSelection::Mix,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InputSource {
+    ///A device records whether we keep up or not, the other sources wait for the DSP
+    pub fn is_live(&self) -> bool {
+        matches!(self, InputSource::Device { .. })
+    }
+
+    ///Builds the backend feeding producer, so frontends do not have to know every backend
+    pub fn open(&self, producer: BridgeProducer) -> Result<Box<dyn AudioBackend>, String> {
+        match self {
+            InputSource::Device { device, channel } => Ok(Box::new(
+                native::NativeAudioBackend::open(producer, device.as_ref(), *channel)?,
+            )),
+            InputSource::File { path, realtime } => Ok(Box::new(file::FileAudioBackend::new(
+                producer, path, *realtime,
+            )?)),
+            InputSource::Generator {
+                signal,
+                amplitude,
+                sample_rate,
+                realtime,
+            } => Ok(Box::new(generator::GeneratorBackend::new(
+                producer,
+                signal.clone(),
+                *amplitude,
+                *sample_rate,
+                *realtime,
+            ))),
+        }
+    }
 }
@@ -1,23 +1,379 @@
 use super::*;
-use cpal::Stream;
+use crate::audio_bridge::BridgeProducer;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use rtrb::Producer;
+use cpal::{
+    FromSample, I24, SampleFormat, SizedSample, Stream, StreamConfig, SupportedStreamConfig, U24,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 pub struct NativeAudioBackend {
     stream: Option<Stream>,
     pub sample_rate: f32,
+    pub device_name: String,
+    pub channels: u16,
+    pub sample_format: SampleFormat,
+}
+
+///Our DSP works on one channel : we either average all channels of a frame, or keep one of them
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ChannelSelection {
+    #[default]
+    Mix,
+    ///Zero based index of the channel to keep
+    Channel(usize),
+}
+
+//On the command line, channels are numbered from 1 like on the audio interface
+impl FromStr for ChannelSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if s == "mix" || s == "mono" {
+            return Ok(ChannelSelection::Mix);
+        }
+        match s.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(ChannelSelection::Channel(n - 1)),
+            _ => Err(format!(
+                "Invalid channel \"{}\": expected mix or a channel number from 1",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ChannelSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelSelection::Mix => write!(f, "Mix"),
+            ChannelSelection::Channel(n) => write!(f, "Channel {}", n + 1),
+        }
+    }
+}
+
+///What cpal tells us about an input device, so a frontend can list them and let the user pick one
+#[derive(Debug, Clone)]
+pub struct InputDeviceInfo {
+    pub index: usize,
+    pub host: String,
+    pub name: String,
+    pub is_default: bool,
+    pub channels: Vec<u16>,
+    pub sample_rates: Vec<(u32, u32)>,
+    pub sample_formats: Vec<String>,
+}
+
+///An input device can be selected by its index in list_input_devices, or by its name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum DeviceSelector {
+    Index(usize),
+    Name(String),
+    ///Exactly this device of this host : two hosts often list a device under the same name
+    Host {
+        host: String,
+        name: String,
+    },
+}
+
+impl DeviceSelector {
+    ///Whether the device listed as info is the one selected, the default device for None
+    pub fn matches(selector: Option<&DeviceSelector>, info: &InputDeviceInfo) -> bool {
+        match selector {
+            Some(DeviceSelector::Index(index)) => info.index == *index,
+            Some(DeviceSelector::Name(name)) => info.name == *name,
+            Some(DeviceSelector::Host { host, name }) => info.host == *host && info.name == *name,
+            None => info.is_default,
+        }
+    }
+}
+
+impl FromStr for DeviceSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("Empty device name".to_string());
+        }
+        match s.parse::<usize>() {
+            Ok(index) => Ok(DeviceSelector::Index(index)),
+            Err(_) => Ok(DeviceSelector::Name(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceSelector::Index(index) => write!(f, "#{}", index),
+            DeviceSelector::Name(name) => write!(f, "{}", name),
+            DeviceSelector::Host { host, name } => write!(f, "{} ({})", name, host),
+        }
+    }
+}
+
+impl InputDeviceInfo {
+    ///Selects this very device, whatever the other hosts list
+    pub fn selector(&self) -> DeviceSelector {
+        DeviceSelector::Host {
+            host: self.host.clone(),
+            name: self.name.clone(),
+        }
+    }
+}
+
+impl fmt::Display for InputDeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rates: Vec<String> = self
+            .sample_rates
+            .iter()
+            .map(|&(min, max)| {
+                if min == max {
+                    format!("{}", min)
+                } else {
+                    format!("{}-{}", min, max)
+                }
+            })
+            .collect();
+        let channels: Vec<String> = self.channels.iter().map(|c| c.to_string()).collect();
+        write!(
+            f,
+            "[{}] {}{} ({})\n    channels: {}\n    sample rates: {} Hz\n    formats: {}",
+            self.index,
+            self.name,
+            if self.is_default { " (default)" } else { "" },
+            self.host,
+            channels.join(", "),
+            rates.join(", "),
+            self.sample_formats.join(", ")
+        )
+    }
+}
+
+///Lists the audio hosts available on this platform (ALSA, JACK, CoreAudio, WASAPI...)
+pub fn list_hosts() -> Vec<String> {
+    cpal::available_hosts()
+        .iter()
+        .map(|id| id.name().to_string())
+        .collect()
+}
+
+//We walk every available host, starting with the default one, so indexes stay stable as long as
+//the hardware does not change
+fn input_devices() -> Vec<(String, cpal::Device, bool)> {
+    let default_host = cpal::default_host();
+    let default_id = default_host.id();
+    let mut hosts = vec![default_host];
+    for id in cpal::available_hosts() {
+        if id != default_id
+            && let Ok(host) = cpal::host_from_id(id)
+        {
+            hosts.push(host);
+        }
+    }
+
+    let mut devices = Vec::new();
+    for host in hosts {
+        let default_name = host
+            .default_input_device()
+            .and_then(|d| d.description().ok())
+            .map(|d| d.name().to_string());
+        let host_name = host.id().name().to_string();
+        if let Ok(inputs) = host.input_devices() {
+            for device in inputs {
+                let is_default = host.id() == default_id
+                    && device.description().ok().map(|d| d.name().to_string()) == default_name;
+                devices.push((host_name.clone(), device, is_default));
+            }
+        }
+    }
+    devices
+}
+
+fn device_name(device: &cpal::Device) -> String {
+    device
+        .description()
+        .map(|d| d.name().to_string())
+        .unwrap_or_else(|_| "Unknown device".to_string())
+}
+
+///Enumerates every input device of every host, with what it supports
+pub fn list_input_devices() -> Vec<InputDeviceInfo> {
+    input_devices()
+        .into_iter()
+        .enumerate()
+        .map(|(index, (host, device, is_default))| {
+            let mut channels = Vec::new();
+            let mut sample_rates = Vec::new();
+            let mut sample_formats = Vec::new();
+            if let Ok(configs) = device.supported_input_configs() {
+                for config in configs {
+                    if !channels.contains(&config.channels()) {
+                        channels.push(config.channels());
+                    }
+                    let rates = (config.min_sample_rate(), config.max_sample_rate());
+                    if !sample_rates.contains(&rates) {
+                        sample_rates.push(rates);
+                    }
+                    let format = config.sample_format().to_string();
+                    if !sample_formats.contains(&format) {
+                        sample_formats.push(format);
+                    }
+                }
+            }
+            channels.sort_unstable();
+            sample_rates.sort_unstable();
+            InputDeviceInfo {
+                index,
+                host,
+                name: device_name(&device),
+                is_default,
+                channels,
+                sample_rates,
+                sample_formats,
+            }
+        })
+        .collect()
+}
+
+//An exact name wins, otherwise we accept a case insensitive partial match, as device names are
+//often long ("USB Audio CODEC: Audio (hw:2,0)")
+fn find_device(selector: &DeviceSelector) -> Result<cpal::Device, String> {
+    let devices = input_devices();
+    match selector {
+        DeviceSelector::Index(index) => devices
+            .into_iter()
+            .nth(*index)
+            .map(|(_, device, _)| device)
+            .ok_or(format!("No input device at index {}", index)),
+        DeviceSelector::Name(name) => {
+            let lower = name.to_lowercase();
+            let mut partial = None;
+            for (_, device, _) in devices {
+                let device_name = device_name(&device);
+                if device_name == *name {
+                    return Ok(device);
+                }
+                if partial.is_none() && device_name.to_lowercase().contains(&lower) {
+                    partial = Some(device);
+                }
+            }
+            partial.ok_or(format!("No input device matching \"{}\"", name))
+        }
+        DeviceSelector::Host { host, name } => devices
+            .into_iter()
+            .find(|(device_host, device, _)| device_host == host && device_name(device) == *name)
+            .map(|(_, device, _)| device)
+            .ok_or(format!("No input device \"{}\" on {}", name, host)),
+    }
 }
 
 impl NativeAudioBackend {
-    pub fn new(mut producer: Producer<f32>) -> Result<Self, String> {
-        let host = cpal::default_host();
-        let device = host.default_input_device().ok_or("No input device")?;
+    pub fn new(producer: BridgeProducer) -> Result<Self, String> {
+        Self::open(producer, None, ChannelSelection::Mix)
+    }
+
+    ///Opens the input device matching selector, or the default one, keeping the channel selected
+    pub fn open(
+        producer: BridgeProducer,
+        selector: Option<&DeviceSelector>,
+        channel: ChannelSelection,
+    ) -> Result<Self, String> {
+        let device = match selector {
+            Some(selector) => find_device(selector)?,
+            None => cpal::default_host()
+                .default_input_device()
+                .ok_or("No input device")?,
+        };
 
         let config = device
             .default_input_config()
             .map_err(|e| format!("Failed to get config: {}", e))?;
+        let config = match channel {
+            ChannelSelection::Channel(n) if n >= config.channels() as usize => {
+                Self::config_with_channel(&device, &config, n)?
+            }
+            _ => config,
+        };
 
         let sample_rate = config.sample_rate() as f32;
+        let sample_format = config.sample_format();
+        let channels = config.channels();
+
+        //cpal gives us samples in the device native format : we monomorphize our callback for
+        //each of them, so the conversion to f32 is resolved at compile time
+        let config: StreamConfig = config.into();
+        let stream = match sample_format {
+            SampleFormat::I8 => Self::build_stream::<i8>(&device, &config, channel, producer),
+            SampleFormat::I16 => Self::build_stream::<i16>(&device, &config, channel, producer),
+            SampleFormat::I24 => Self::build_stream::<I24>(&device, &config, channel, producer),
+            SampleFormat::I32 => Self::build_stream::<i32>(&device, &config, channel, producer),
+            SampleFormat::I64 => Self::build_stream::<i64>(&device, &config, channel, producer),
+            SampleFormat::U8 => Self::build_stream::<u8>(&device, &config, channel, producer),
+            SampleFormat::U16 => Self::build_stream::<u16>(&device, &config, channel, producer),
+            SampleFormat::U24 => Self::build_stream::<U24>(&device, &config, channel, producer),
+            SampleFormat::U32 => Self::build_stream::<u32>(&device, &config, channel, producer),
+            SampleFormat::U64 => Self::build_stream::<u64>(&device, &config, channel, producer),
+            SampleFormat::F32 => Self::build_stream::<f32>(&device, &config, channel, producer),
+            SampleFormat::F64 => Self::build_stream::<f64>(&device, &config, channel, producer),
+            format => Err(format!("Unsupported sample format {}", format)),
+        }?;
+
+        Ok(Self {
+            stream: Some(stream),
+            sample_rate,
+            device_name: device_name(&device),
+            channels,
+            sample_format,
+        })
+    }
+
+    //The default config of a multichannel interface often has only 2 channels : we look for
+    //the smallest one holding channel n, at the default sample rate and format if it can
+    fn config_with_channel(
+        device: &cpal::Device,
+        default: &SupportedStreamConfig,
+        n: usize,
+    ) -> Result<SupportedStreamConfig, String> {
+        let configs: Vec<_> = device
+            .supported_input_configs()
+            .map_err(|e| format!("Failed to get configs: {}", e))?
+            .collect();
+        let max_channels = configs.iter().map(|c| c.channels()).max().unwrap_or(0);
+        configs
+            .into_iter()
+            .filter(|c| c.channels() as usize > n)
+            .min_by_key(|c| {
+                (
+                    c.sample_format() != default.sample_format(),
+                    c.try_with_sample_rate(default.sample_rate()).is_none(),
+                    c.channels(),
+                )
+            })
+            .map(|c| {
+                c.try_with_sample_rate(default.sample_rate())
+                    .unwrap_or_else(|| c.with_max_sample_rate())
+            })
+            .ok_or(format!(
+                "Channel {} requested but the device only has {}",
+                n + 1,
+                max_channels
+            ))
+    }
+
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        channel: ChannelSelection,
+        mut producer: BridgeProducer,
+    ) -> Result<Stream, String>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        let channels = config.channels.max(1) as usize;
 
         //move on a closure force the closure to capture variables by value : here it captures
         //producer taking its ownership. Moving producer make it usable only inside this closure,
@@ -30,25 +386,30 @@ impl NativeAudioBackend {
         //On native, we could add one more thread for the DSP to not block the UI.
         //But on wasm, we only have the main thread for the UI and DSP, so we will have to optimize
         //compute of DSP to keep real time rendering
-        //data is the samples themself, the slice is provided by InputCallbackInfo
-        //We can iterate in data to get samples and push them in the ringbuf
-        let stream = device
+        //data is the samples themself, interleaved : one frame holds one sample per channel.
+        //We reduce each frame to one f32 sample and push it in the ringbuf, without allocating
+        device
             .build_input_stream(
-                &config.into(),
-                move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                    for &sample in data {
-                        let _ = producer.push(sample);
-                    }
+                config,
+                move |data: &[T], _: &cpal::InputCallbackInfo| {
+                    producer.callback(|producer| {
+                        for frame in data.chunks_exact(channels) {
+                            let sample = match channel {
+                                ChannelSelection::Mix => {
+                                    frame.iter().map(|&s| s.to_sample::<f32>()).sum::<f32>()
+                                        / channels as f32
+                                }
+                                ChannelSelection::Channel(n) => frame[n].to_sample::<f32>(),
+                            };
+                            //a full ringbuff drops the sample, the bridge stats count it
+                            producer.push(sample);
+                        }
+                    });
                 },
                 move |err| eprintln!("cpal input error: {:?}", err),
                 None,
             )
-            .map_err(|e| format!("Failed to build stream: {}", e))?;
-
-        Ok(Self {
-            stream: Some(stream),
-            sample_rate,
-        })
+            .map_err(|e| format!("Failed to build stream: {}", e))
     }
 }
 
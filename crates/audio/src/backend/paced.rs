@@ -0,0 +1,160 @@
+use super::*;
+use crate::audio_bridge::BridgeProducer;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+//samples pushed at once : small enough to keep a steady pace, big enough to not wake up too often
+const CHUNK_SIZE: usize = 512;
+
+///Anything that can produce samples on demand, like a file or a generator.
+///fill writes at most out.len() samples and returns how many, 0 meaning the source is exhausted
+pub trait SampleSource: Send + 'static {
+    fn fill(&mut self, out: &mut [f32]) -> usize;
+}
+
+///Plays a SampleSource into the ringbuf from its own thread, as a microphone would.
+///With realtime, samples are pushed at sample_rate, otherwise as fast as the consumer frees room in
+///the ringbuf. This thread never drops samples : when the ringbuf is full it waits for the DSP.
+pub struct PacedBackend<S: SampleSource> {
+    source: Option<S>,
+    producer: Option<BridgeProducer>,
+    pub sample_rate: f32,
+    realtime: bool,
+    running: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+    //the playback thread gives back the producer and the source, so we can resume where it stopped
+    thread: Option<JoinHandle<(BridgeProducer, S)>>,
+}
+
+impl<S: SampleSource> PacedBackend<S> {
+    pub fn from_source(
+        producer: BridgeProducer,
+        source: S,
+        sample_rate: f32,
+        realtime: bool,
+    ) -> Self {
+        Self {
+            source: Some(source),
+            producer: Some(producer),
+            sample_rate,
+            realtime,
+            running: Arc::new(AtomicBool::new(false)),
+            finished: Arc::new(AtomicBool::new(false)),
+            thread: None,
+        }
+    }
+
+    ///The source, when playback is stopped
+    pub fn source(&self) -> Option<&S> {
+        self.source.as_ref()
+    }
+
+    fn play(
+        mut producer: BridgeProducer,
+        mut source: S,
+        sample_rate: f32,
+        realtime: bool,
+        running: Arc<AtomicBool>,
+        finished: Arc<AtomicBool>,
+    ) -> (BridgeProducer, S) {
+        let mut buffer = [0.0f32; CHUNK_SIZE];
+        let start = Instant::now();
+        let mut pushed: u64 = 0;
+
+        while running.load(Ordering::Relaxed) {
+            let n = CHUNK_SIZE.min(producer.slots());
+            if n == 0 {
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+            let n = source.fill(&mut buffer[..n]);
+            if n == 0 {
+                finished.store(true, Ordering::Relaxed);
+                break;
+            }
+            producer.callback(|producer| producer.push_slice(&buffer[..n]));
+            pushed += n as u64;
+
+            if realtime {
+                let due = Duration::from_secs_f64(pushed as f64 / sample_rate as f64);
+                let elapsed = start.elapsed();
+                if due > elapsed {
+                    std::thread::sleep(due - elapsed);
+                }
+            }
+        }
+        (producer, source)
+    }
+}
+
+impl<S: SampleSource> AudioBackend for PacedBackend<S> {
+    fn start(&mut self) -> Result<(), String> {
+        if self.thread.is_some() {
+            return Ok(());
+        }
+        let (Some(producer), Some(source)) = (self.producer.take(), self.source.take()) else {
+            return Err("Backend lost its producer".to_string());
+        };
+        let running = Arc::clone(&self.running);
+        let finished = Arc::clone(&self.finished);
+        let (sample_rate, realtime) = (self.sample_rate, self.realtime);
+        //handed over through a slot rather than moved in the closure, so a failed spawn, which
+        //drops the closure, does not take them with it
+        let handoff = Arc::new(Mutex::new(Some((producer, source))));
+        let thread_handoff = Arc::clone(&handoff);
+
+        self.running.store(true, Ordering::Relaxed);
+        let spawned = std::thread::Builder::new()
+            .name("paced playback".to_string())
+            .spawn(move || {
+                let (producer, source) = thread_handoff
+                    .lock()
+                    .ok()
+                    .and_then(|mut handoff| handoff.take())
+                    .expect("the playback thread gets the producer");
+                Self::play(producer, source, sample_rate, realtime, running, finished)
+            });
+        match spawned {
+            Ok(thread) => {
+                self.thread = Some(thread);
+                Ok(())
+            }
+            Err(e) => {
+                self.running.store(false, Ordering::Relaxed);
+                if let Some((producer, source)) =
+                    handoff.lock().ok().and_then(|mut handoff| handoff.take())
+                {
+                    self.producer = Some(producer);
+                    self.source = Some(source);
+                }
+                Err(format!("Failed to spawn playback thread: {}", e))
+            }
+        }
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take()
+            && let Ok((producer, source)) = thread.join()
+        {
+            self.producer = Some(producer);
+            self.source = Some(source);
+        }
+    }
+
+    fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+}
+
+impl<S: SampleSource> Drop for PacedBackend<S> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -1,5 +1,5 @@
+use crate::audio_bridge::BridgeProducer;
 use crate::backend::AudioBackend;
-use rtrb::Producer;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::prelude::*;
 use web_sys::MediaStreamConstraints;
@@ -17,7 +17,7 @@ pub struct WasmAudioBackend {
 // https://developer.mozilla.org/fr/docs/Web/API/AudioWorklet
 
 impl WasmAudioBackend {
-    pub async fn new(producer: Producer<f32>) -> Result<Self, String> {
+    pub async fn new(producer: BridgeProducer) -> Result<Self, String> {
         //end point for web audio : can fail if the browser block audio permissions
         let audio_context =
             AudioContext::new().map_err(|e| format!("Failed to create AudioContext: {:?}", e))?;
@@ -113,27 +113,15 @@ impl WasmAudioBackend {
     ///Receive samples sent by AudioWorklet and push them in the ringbuf
     fn setup_message_handler(
         worklet_node: &AudioWorkletNode,
-        mut producer: Producer<f32>,
+        mut producer: BridgeProducer,
     ) -> Result<(), String> {
         // Plus besoin d'allouer un Vec permanent ici
 
         let closure = Closure::wrap(Box::new(move |event: web_sys::MessageEvent| {
             if let Ok(array) = event.data().dyn_into::<js_sys::Float32Array>() {
                 let samples = array.to_vec();
-                let n = samples.len().min(producer.slots());
-
-                if n > 0
-                    && let Ok(chunk) = producer.write_chunk_uninit(n)
-                {
-                    let written = chunk.fill_from_iter(samples.iter().take(n).copied());
-
-                    if written < samples.len() {
-                        web_sys::console::warn_1(
-                            &format!("Buffer full, dropped {} samples", samples.len() - written)
-                                .into(),
-                        );
-                    }
-                }
+                //what does not fit is counted as dropped in the bridge stats
+                producer.callback(|producer| producer.push_slice(&samples));
             }
         }) as Box<dyn FnMut(_)>);
 
@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+use std::fmt;
+use std::str::FromStr;
+
+///One partial of a multi-tone signal
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Tone {
+    pub frequency: f32,
+    pub amplitude: f32,
+}
+
+///The signals we can synthesize. Periodic ones are not band limited : harmonics above Nyquist
+///alias, which is fine to exercise the DSP but not to listen to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Signal {
+    Sine(f32),
+    Square(f32),
+    Saw(f32),
+    Triangle(f32),
+    WhiteNoise,
+    PinkNoise,
+    ///Exponential sweep from start to end Hz over duration seconds, then starts again
+    Chirp {
+        start: f32,
+        end: f32,
+        duration: f32,
+    },
+    MultiTone(Vec<Tone>),
+}
+
+//Parses the same syntax the CLI accepts : "sine:440", "white", "chirp:100:2000:5",
+//"tones:440,660:0.5,880" (an optional amplitude can follow each tone frequency)
+impl FromStr for Signal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let kind = parts.next().unwrap_or_default().to_lowercase();
+        let args: Vec<&str> = parts.collect();
+        let number = |i: usize, default: f32| -> Result<f32, String> {
+            match args.get(i) {
+                Some(v) => v
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("Invalid number \"{}\" in \"{}\"", v, s)),
+                None => Ok(default),
+            }
+        };
+        //a frequency or a duration of 0 or less would output silence, or NaN for a chirp
+        let positive = |i: usize, default: f32| -> Result<f32, String> {
+            let value = number(i, default)?;
+            if value.is_finite() && value > 0.0 {
+                Ok(value)
+            } else {
+                Err(format!("\"{}\" needs to be positive in \"{}\"", value, s))
+            }
+        };
+        match kind.as_str() {
+            "sine" => Ok(Signal::Sine(positive(0, 440.0)?)),
+            "square" => Ok(Signal::Square(positive(0, 440.0)?)),
+            "saw" => Ok(Signal::Saw(positive(0, 440.0)?)),
+            "triangle" => Ok(Signal::Triangle(positive(0, 440.0)?)),
+            "white" => Ok(Signal::WhiteNoise),
+            "pink" => Ok(Signal::PinkNoise),
+            "chirp" => Ok(Signal::Chirp {
+                start: positive(0, 20.0)?,
+                end: positive(1, 20000.0)?,
+                duration: positive(2, 10.0)?,
+            }),
+            "tones" => {
+                let list = s.trim().split_once(':').map(|(_, l)| l).unwrap_or_default();
+                let tones = list
+                    .split(',')
+                    .filter(|t| !t.trim().is_empty())
+                    .map(|t| {
+                        let mut values = t.split(':').map(|v| v.trim().parse::<f32>());
+                        match (values.next(), values.next()) {
+                            (Some(Ok(frequency)), _)
+                                if !(frequency.is_finite() && frequency > 0.0) =>
+                            {
+                                Err(format!(
+                                    "Invalid tone \"{}\", the frequency needs to be positive",
+                                    t
+                                ))
+                            }
+                            (Some(Ok(frequency)), None) => Ok(Tone {
+                                frequency,
+                                amplitude: 1.0,
+                            }),
+                            (Some(Ok(frequency)), Some(Ok(amplitude))) => Ok(Tone {
+                                frequency,
+                                amplitude,
+                            }),
+                            _ => Err(format!("Invalid tone \"{}\"", t)),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if tones.is_empty() {
+                    return Err("tones needs at least one frequency".to_string());
+                }
+                Ok(Signal::MultiTone(tones))
+            }
+            _ => Err(format!(
+                "Unknown signal \"{}\" (sine, square, saw, triangle, white, pink, chirp, tones)",
+                kind
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Signal::Sine(freq) => write!(f, "sine {} Hz", freq),
+            Signal::Square(freq) => write!(f, "square {} Hz", freq),
+            Signal::Saw(freq) => write!(f, "saw {} Hz", freq),
+            Signal::Triangle(freq) => write!(f, "triangle {} Hz", freq),
+            Signal::WhiteNoise => write!(f, "white noise"),
+            Signal::PinkNoise => write!(f, "pink noise"),
+            Signal::Chirp {
+                start,
+                end,
+                duration,
+            } => write!(f, "chirp {}-{} Hz over {} s", start, end, duration),
+            Signal::MultiTone(tones) => {
+                let freqs: Vec<String> = tones.iter().map(|t| t.frequency.to_string()).collect();
+                write!(f, "tones {} Hz", freqs.join(", "))
+            }
+        }
+    }
+}
+
+///Produces the samples of a Signal, deterministically : the noise comes from a seeded xorshift,
+///so two generators built the same way output the same samples
+pub struct SignalGenerator {
+    signal: Signal,
+    amplitude: f32,
+    sample_rate: f32,
+    //phases are in cycles [0, 1), one per tone for MultiTone
+    phases: Vec<f64>,
+    //samples since the beginning of the current chirp sweep
+    elapsed: u64,
+    rng: u32,
+    //Paul Kellet's pink noise filter state
+    pink: [f32; 7],
+}
+
+impl SignalGenerator {
+    pub fn new(signal: Signal, amplitude: f32, sample_rate: f32) -> Self {
+        let voices = match &signal {
+            Signal::MultiTone(tones) => tones.len(),
+            _ => 1,
+        };
+        Self {
+            signal,
+            amplitude,
+            sample_rate,
+            phases: vec![0.0; voices],
+            elapsed: 0,
+            rng: 0x9E37_79B9,
+            pink: [0.0; 7],
+        }
+    }
+
+    pub fn signal(&self) -> &Signal {
+        &self.signal
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    fn white(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 17;
+        self.rng ^= self.rng << 5;
+        (self.rng as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+
+    fn advance(phase: &mut f64, frequency: f64, sample_rate: f64) -> f64 {
+        let current = *phase;
+        *phase = (*phase + frequency / sample_rate).fract();
+        current
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let sr = self.sample_rate as f64;
+        let value = match self.signal {
+            Signal::Sine(freq) => {
+                let p = Self::advance(&mut self.phases[0], freq as f64, sr);
+                (p * TAU).sin() as f32
+            }
+            Signal::Square(freq) => {
+                let p = Self::advance(&mut self.phases[0], freq as f64, sr);
+                if p < 0.5 { 1.0 } else { -1.0 }
+            }
+            Signal::Saw(freq) => {
+                let p = Self::advance(&mut self.phases[0], freq as f64, sr);
+                (2.0 * p - 1.0) as f32
+            }
+            Signal::Triangle(freq) => {
+                let p = Self::advance(&mut self.phases[0], freq as f64, sr);
+                (1.0 - 4.0 * (p - 0.5).abs()) as f32
+            }
+            Signal::WhiteNoise => self.white(),
+            Signal::PinkNoise => {
+                let white = self.white();
+                let b = &mut self.pink;
+                b[0] = 0.99886 * b[0] + white * 0.0555179;
+                b[1] = 0.99332 * b[1] + white * 0.0750759;
+                b[2] = 0.96900 * b[2] + white * 0.153852;
+                b[3] = 0.86650 * b[3] + white * 0.3104856;
+                b[4] = 0.55000 * b[4] + white * 0.5329522;
+                b[5] = -0.7616 * b[5] - white * 0.0168980;
+                let pink = b.iter().sum::<f32>() + white * 0.5362;
+                b[6] = white * 0.115926;
+                //the filter has a gain of about 4.5, we bring it back around [-1, 1]
+                pink * 0.2
+            }
+            Signal::Chirp {
+                start,
+                end,
+                duration,
+            } => {
+                let length = (duration as f64 * sr).max(1.0) as u64;
+                if self.elapsed >= length {
+                    self.elapsed = 0;
+                }
+                let t = self.elapsed as f64 / length as f64;
+                let freq = start as f64 * (end as f64 / start as f64).powf(t);
+                self.elapsed += 1;
+                let p = Self::advance(&mut self.phases[0], freq, sr);
+                (p * TAU).sin() as f32
+            }
+            Signal::MultiTone(ref tones) => {
+                let total: f32 = tones.iter().map(|t| t.amplitude.abs()).sum();
+                let mut sum = 0.0;
+                for (tone, phase) in tones.iter().zip(self.phases.iter_mut()) {
+                    let p = Self::advance(phase, tone.frequency as f64, sr);
+                    sum += tone.amplitude * (p * TAU).sin() as f32;
+                }
+                //normalized so the mix never exceeds the requested amplitude
+                if total > 0.0 { sum / total } else { 0.0 }
+            }
+        };
+        value * self.amplitude
+    }
+
+    pub fn fill(&mut self, buffer: &mut [f32]) {
+        for sample in buffer.iter_mut() {
+            *sample = self.next_sample();
+        }
+    }
+}
+
+impl Iterator for SignalGenerator {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        Some(self.next_sample())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_signals() {
+        assert_eq!("sine:440".parse(), Ok(Signal::Sine(440.0)));
+        assert_eq!(
+            "chirp:100:2000:5".parse(),
+            Ok(Signal::Chirp {
+                start: 100.0,
+                end: 2000.0,
+                duration: 5.0
+            })
+        );
+    }
+
+    //a chirp from 0 Hz would output NaN forever
+    #[test]
+    fn rejects_non_positive_values() {
+        for s in [
+            "sine:0",
+            "square:-440",
+            "saw:nan",
+            "triangle:inf",
+            "chirp:0:2000:5",
+            "chirp:100:-1:5",
+            "chirp:100:2000:0",
+            "tones:440,0",
+            "tones:-440:0.5",
+        ] {
+            assert!(s.parse::<Signal>().is_err(), "{} was accepted", s);
+        }
+    }
+}
@@ -1,7 +1,22 @@
 pub mod audio_bridge;
 pub mod backend;
+pub mod generator;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod recorder;
+pub mod wav;
 
 #[cfg(not(target_arch = "wasm32"))]
-pub use backend::native::NativeAudioBackend;
+pub use backend::InputSource;
+#[cfg(not(target_arch = "wasm32"))]
+pub use backend::file::FileAudioBackend;
+#[cfg(not(target_arch = "wasm32"))]
+pub use backend::generator::GeneratorBackend;
+#[cfg(not(target_arch = "wasm32"))]
+pub use backend::native::{
+    ChannelSelection, DeviceSelector, InputDeviceInfo, NativeAudioBackend, list_hosts,
+    list_input_devices,
+};
 #[cfg(target_arch = "wasm32")]
 pub use backend::wasm::WasmAudioBackend;
+#[cfg(not(target_arch = "wasm32"))]
+pub use recorder::{RecordTap, Recorder};
@@ -0,0 +1,198 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rtrb::{Consumer, Producer, RingBuffer};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+struct TapShared {
+    //the callback only copies samples to the tap while a recorder listens
+    enabled: AtomicBool,
+    //samples lost because the recorder thread did not keep up
+    dropped: AtomicU64,
+}
+
+///The audio side of the recording tap : a second ringbuff the callback copies its samples to,
+///only with atomics and a preallocated buffer, so recording adds no allocation to the callback
+pub struct TapProducer {
+    producer: Producer<f32>,
+    shared: Arc<TapShared>,
+}
+
+impl TapProducer {
+    pub fn push(&mut self, sample: f32) {
+        if self.shared.enabled.load(Ordering::Relaxed) && self.producer.push(sample).is_err() {
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn push_slice(&mut self, samples: &[f32]) {
+        if !self.shared.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let n = samples.len().min(self.producer.slots());
+        if n > 0
+            && let Ok(chunk) = self.producer.write_chunk_uninit(n)
+        {
+            chunk.fill_from_iter(samples[..n].iter().copied());
+        }
+        self.shared
+            .dropped
+            .fetch_add((samples.len() - n) as u64, Ordering::Relaxed);
+    }
+}
+
+///The other end of the tap, for the ui to hand to a Recorder. Cloning it is cheap : only the
+///recorder thread ever locks the consumer, never the audio callback
+#[derive(Clone)]
+pub struct RecordTap {
+    consumer: Arc<Mutex<Consumer<f32>>>,
+    shared: Arc<TapShared>,
+}
+
+pub fn tap(capacity: usize) -> (TapProducer, RecordTap) {
+    let (producer, consumer) = RingBuffer::new(capacity);
+    let shared = Arc::new(TapShared {
+        enabled: AtomicBool::new(false),
+        dropped: AtomicU64::new(0),
+    });
+    (
+        TapProducer {
+            producer,
+            shared: shared.clone(),
+        },
+        RecordTap {
+            consumer: Arc::new(Mutex::new(consumer)),
+            shared,
+        },
+    )
+}
+
+///Writes what the tap receives to a mono 32 bits float WAV file, on its own thread
+pub struct Recorder {
+    path: PathBuf,
+    sample_rate: f32,
+    shared: Arc<TapShared>,
+    running: Arc<AtomicBool>,
+    written: Arc<AtomicU64>,
+    thread: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl Recorder {
+    ///Creates the file and starts recording, sample_rate is the one of the backend feeding the tap
+    pub fn start(tap: &RecordTap, path: &Path, sample_rate: f32) -> Result<Self, String> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: sample_rate.round() as u32,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(path, spec)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+        //what a previous recording left in the tap does not belong to this one
+        {
+            let mut consumer = tap
+                .consumer
+                .lock()
+                .map_err(|_| "The recording tap is poisoned".to_string())?;
+            let stale = consumer.slots();
+            if let Ok(chunk) = consumer.read_chunk(stale) {
+                chunk.commit_all();
+            }
+        }
+        tap.shared.dropped.store(0, Ordering::Relaxed);
+        tap.shared.enabled.store(true, Ordering::Relaxed);
+
+        let running = Arc::new(AtomicBool::new(true));
+        let written = Arc::new(AtomicU64::new(0));
+        let consumer = tap.consumer.clone();
+        let thread_running = running.clone();
+        let thread_written = written.clone();
+        let display = path.display().to_string();
+        let thread = std::thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || {
+                let mut consumer = consumer
+                    .lock()
+                    .map_err(|_| "The recording tap is poisoned".to_string())?;
+                loop {
+                    //once stopped, we still write what is left in the tap
+                    let stopping = !thread_running.load(Ordering::Relaxed);
+                    let available = consumer.slots();
+                    if available == 0 {
+                        if stopping {
+                            break;
+                        }
+                        std::thread::sleep(Duration::from_millis(10));
+                        continue;
+                    }
+                    if let Ok(chunk) = consumer.read_chunk(available) {
+                        let (first, second) = chunk.as_slices();
+                        for &sample in first.iter().chain(second) {
+                            writer
+                                .write_sample(sample)
+                                .map_err(|e| format!("Failed to write {}: {}", display, e))?;
+                        }
+                        chunk.commit_all();
+                    }
+                    thread_written.fetch_add(available as u64, Ordering::Relaxed);
+                }
+                writer
+                    .finalize()
+                    .map_err(|e| format!("Failed to finalize {}: {}", display, e))
+            })
+            .map_err(|e| format!("Failed to start the recorder thread: {}", e))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            sample_rate,
+            shared: tap.shared.clone(),
+            running,
+            written,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    ///Length of what was written so far
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(
+            self.written.load(Ordering::Relaxed) as f64 / self.sample_rate as f64,
+        )
+    }
+
+    ///Samples missing from the file because the recorder fell behind
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    ///Stops the tap, writes what is left and closes the file
+    pub fn stop(mut self) -> Result<(), String> {
+        self.finish()
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.shared.enabled.store(false, Ordering::Relaxed);
+        self.running.store(false, Ordering::Relaxed);
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| "The recorder thread panicked".to_string())?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    //a dropped recorder still leaves a valid file behind
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            eprintln!("{}", e);
+        }
+    }
+}
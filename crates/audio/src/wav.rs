@@ -0,0 +1,66 @@
+use hound::{SampleFormat, WavReader};
+use std::path::Path;
+
+///A decoded file : samples are mono f32 in [-1.0, 1.0], like the ones our backends push
+pub struct WavData {
+    pub samples: Vec<f32>,
+    pub sample_rate: f32,
+    pub channels: u16,
+}
+
+///A decoded file as it is stored : interleaved frames of one sample per channel, in [-1.0, 1.0]
+pub struct WavFrames {
+    pub interleaved: Vec<f32>,
+    pub sample_rate: f32,
+    pub channels: u16,
+}
+
+impl WavFrames {
+    ///Our DSP works on a single channel, so we average channels instead of keeping the frames
+    pub fn downmix(&self) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        self.interleaved
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+}
+
+///Decodes a whole WAV file (PCM 8/16/24/32 bits or 32 bits float) and downmixes it to mono
+pub fn read_wav(path: &Path) -> Result<WavData, String> {
+    let frames = read_wav_frames(path)?;
+    Ok(WavData {
+        samples: frames.downmix(),
+        sample_rate: frames.sample_rate,
+        channels: frames.channels,
+    })
+}
+
+///Decodes a whole WAV file and keeps every channel, for what must not be measured on the downmix
+pub fn read_wav_frames(path: &Path) -> Result<WavFrames, String> {
+    let mut reader =
+        WavReader::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let spec = reader.spec();
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?,
+        SampleFormat::Int => {
+            //integer samples are signed and scaled on bits_per_sample bits
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))?
+        }
+    };
+
+    Ok(WavFrames {
+        interleaved,
+        sample_rate: spec.sample_rate as f32,
+        channels: spec.channels.max(1),
+    })
+}
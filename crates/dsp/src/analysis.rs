@@ -0,0 +1,172 @@
+use crate::instrument::StringTracker;
+use crate::metering::MeterReading;
+use crate::note::{self, Note};
+use crate::pitch::PitchAlgorithm;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+///Every result of one DSP update, what a frontend needs to draw a frame.
+///Buffers are shared behind Arc, so cloning a snapshot to hand it to the ui is cheap
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    ///Where the analysed window ends in the stream, in seconds since the start
+    pub time: f64,
+    pub rms: f32,
+    pub peak: f32,
+    pub frequency: Option<f32>,
+    pub note: Option<Note>,
+    pub clarity: Option<f32>,
+    pub sample_rate: f32,
+    pub meter: MeterReading,
+    pub a4: f32,
+    pub pitch_algorithm: PitchAlgorithm,
+    pub strings: Option<StringTracker>,
+    ///Latest samples, for the waveform
+    pub samples: Arc<[f32]>,
+    ///Magnitudes in dBFS from 0 Hz to Nyquist
+    pub spectrum: Arc<[f32]>,
+    ///Spectrum frames, oldest first, shared with the DSP until its next frame
+    pub spectrogram: Arc<VecDeque<Arc<[f32]>>>,
+    pub spectrogram_capacity: usize,
+    ///Samples the DSP skipped to catch up when it fell behind
+    pub skipped_samples: u64,
+    ///Updates that found the ringbuff empty
+    pub underruns: u64,
+}
+
+impl Default for Analysis {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            rms: 0.0,
+            peak: 0.0,
+            frequency: None,
+            note: None,
+            clarity: None,
+            sample_rate: 48000.0,
+            meter: MeterReading::default(),
+            a4: note::DEFAULT_A4,
+            pitch_algorithm: PitchAlgorithm::Yin,
+            strings: None,
+            samples: Arc::from([]),
+            spectrum: Arc::from([]),
+            spectrogram: Arc::default(),
+            spectrogram_capacity: 1,
+            skipped_samples: 0,
+            underruns: 0,
+        }
+    }
+}
+
+//a DSP publishes a snapshot per hop to a triple buffer, and the ui keeps one or two : that many
+//buffers are enough for one to be free again at each hop
+const POOL_SIZE: usize = 8;
+
+///The Arc buffers handed out in snapshots, taken back once every reader dropped them, so
+///publishing a snapshot per hop copies the samples without allocating
+#[derive(Default)]
+pub(crate) struct BufferPool {
+    buffers: Vec<Arc<[f32]>>,
+}
+
+impl BufferPool {
+    pub(crate) fn share(&mut self, data: &[f32]) -> Arc<[f32]> {
+        for buffer in &mut self.buffers {
+            if buffer.len() == data.len()
+                && let Some(free) = Arc::get_mut(buffer)
+            {
+                free.copy_from_slice(data);
+                return Arc::clone(buffer);
+            }
+        }
+        //the ones of another size are left from before a resize
+        if self.buffers.len() >= POOL_SIZE {
+            self.buffers.retain(|b| b.len() == data.len());
+        }
+        if self.buffers.len() >= POOL_SIZE {
+            self.buffers.remove(0);
+        }
+        let buffer: Arc<[f32]> = Arc::from(data);
+        self.buffers.push(Arc::clone(&buffer));
+        buffer
+    }
+}
+
+///Picks count samples evenly spread over buffer, for a display narrower than the buffer
+pub fn decimate(buffer: &[f32], count: usize) -> Vec<f32> {
+    if buffer.is_empty() {
+        return Vec::new();
+    }
+    if count >= buffer.len() {
+        return buffer.to_vec();
+    }
+    let step = buffer.len() as f32 / count as f32;
+    (0..count)
+        .map(|i| (i as f32 * step) as usize)
+        .filter(|&index| index < buffer.len())
+        .map(|index| buffer[index])
+        .collect()
+}
+
+///Lowest and highest sample of one column of a display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Envelope {
+    pub min: f32,
+    pub max: f32,
+}
+
+///Splits buffer in count columns and keeps the lowest and highest sample of each : unlike
+///decimate, a spike or a clipped sample shows whatever the zoom. With fewer samples than
+///columns, each sample gets its own column
+pub fn envelope(buffer: &[f32], count: usize) -> Vec<Envelope> {
+    if buffer.is_empty() || count == 0 {
+        return Vec::new();
+    }
+    let count = count.min(buffer.len());
+    (0..count)
+        .map(|i| {
+            let start = i * buffer.len() / count;
+            let end = ((i + 1) * buffer.len() / count).max(start + 1);
+            buffer[start..end].iter().fold(
+                Envelope {
+                    min: f32::INFINITY,
+                    max: f32::NEG_INFINITY,
+                },
+                |envelope, &s| Envelope {
+                    min: envelope.min.min(s),
+                    max: envelope.max.max(s),
+                },
+            )
+        })
+        .collect()
+}
+
+impl Analysis {
+    pub fn get_rms(&self) -> f32 {
+        self.rms
+    }
+
+    pub fn get_samples(&self, count: usize) -> Vec<f32> {
+        decimate(&self.samples, count)
+    }
+
+    pub fn get_envelope(&self, count: usize) -> Vec<Envelope> {
+        envelope(&self.samples, count)
+    }
+
+    pub fn get_spectrum(&self) -> &[f32] {
+        &self.spectrum
+    }
+
+    pub fn get_frequency(&self) -> Option<f32> {
+        self.frequency
+    }
+
+    pub fn get_note(&self) -> Option<Note> {
+        self.note
+    }
+
+    pub fn get_clarity(&self) -> Option<f32> {
+        self.clarity
+    }
+}
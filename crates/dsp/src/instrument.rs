@@ -0,0 +1,160 @@
+use crate::note::{Note, parse_note};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock};
+
+//id for the command line, name for the ui, strings from the lowest
+const PRESETS: [(&str, &str, &str); 13] = [
+    ("guitar", "Guitar standard", "E2 A2 D3 G3 B3 E4"),
+    ("drop-d", "Guitar drop D", "D2 A2 D3 G3 B3 E4"),
+    ("dadgad", "Guitar DADGAD", "D2 A2 D3 G3 A3 D4"),
+    ("open-g", "Guitar open G", "D2 G2 D3 G3 B3 D4"),
+    ("bass", "Bass 4 strings", "E1 A1 D2 G2"),
+    ("bass5", "Bass 5 strings", "B0 E1 A1 D2 G2"),
+    ("bass6", "Bass 6 strings", "B0 E1 A1 D2 G2 C3"),
+    ("ukulele", "Ukulele", "G4 C4 E4 A4"),
+    ("baritone-ukulele", "Baritone ukulele", "D3 G3 B3 E4"),
+    ("violin", "Violin", "G3 D4 A4 E5"),
+    ("viola", "Viola", "C3 G3 D4 A4"),
+    ("cello", "Cello", "C2 G2 D3 A3"),
+    ("double-bass", "Double bass", "E1 A1 D2 G2"),
+];
+
+//parsed once, the ui lists them on every frame
+static PRESET_TUNINGS: LazyLock<Vec<InstrumentTuning>> = LazyLock::new(|| {
+    PRESETS
+        .iter()
+        .filter_map(|(_, name, strings)| InstrumentTuning::custom(name, strings).ok())
+        .collect()
+});
+
+///The open strings of an instrument, as MIDI notes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstrumentTuning {
+    pub name: String,
+    pub strings: Vec<i32>,
+}
+
+impl InstrumentTuning {
+    pub fn presets() -> &'static [InstrumentTuning] {
+        &PRESET_TUNINGS
+    }
+
+    ///A user defined tuning, from notes like "E2 A2 D3" or "E2,A2,D3"
+    pub fn custom(name: &str, strings: &str) -> Result<Self, String> {
+        let strings = strings
+            .split([' ', ','])
+            .filter(|s| !s.trim().is_empty())
+            .map(parse_note)
+            .collect::<Result<Vec<_>, _>>()?;
+        if strings.is_empty() {
+            return Err("A tuning needs at least one string".to_string());
+        }
+        Ok(Self {
+            name: name.to_string(),
+            strings,
+        })
+    }
+
+    ///The string closest to freq, and the note compared to that string target.
+    ///Targets come from the tuning table, so the temperament and A4 still apply
+    pub fn nearest_string(&self, freq: f32, table: &[Option<f32>]) -> Option<(usize, Note)> {
+        self.strings
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &midi)| {
+                let target = table.get(usize::try_from(midi).ok()?).copied().flatten()?;
+                Note::against(freq, midi, target).map(|note| (index, note))
+            })
+            .min_by(|a, b| a.1.cents.abs().total_cmp(&b.1.cents.abs()))
+    }
+}
+
+//A preset id, or a custom list of notes
+impl FromStr for InstrumentTuning {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((_, name, strings)) =
+            PRESETS.iter().find(|(id, _, _)| id.eq_ignore_ascii_case(s))
+        {
+            return Self::custom(name, strings);
+        }
+        let ids: Vec<&str> = PRESETS.iter().map(|(id, _, _)| *id).collect();
+        Self::custom("Custom", s).map_err(|e| {
+            format!(
+                "{} (expected notes like E2,A2,D3 or one of {})",
+                e,
+                ids.join(", ")
+            )
+        })
+    }
+}
+
+impl fmt::Display for InstrumentTuning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+///Follows an instrument being tuned : which string is played, and which ones are in tune already.
+///The tuning and the flags are shared behind Arc, so a snapshot per hop clones it without
+///allocating : the flags are only copied when one of them changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringTracker {
+    pub tuning: Arc<InstrumentTuning>,
+    ///Index in tuning.strings of the string heard last
+    pub current: Option<usize>,
+    pub tuned: Arc<[bool]>,
+    //a string is marked tuned after a few consecutive in tune detections, not a lucky one
+    streak: usize,
+}
+
+const TUNED_STREAK: usize = 8;
+
+impl StringTracker {
+    pub fn new(tuning: InstrumentTuning) -> Self {
+        let strings = tuning.strings.len();
+        Self {
+            tuning: Arc::new(tuning),
+            current: None,
+            tuned: Arc::from(vec![false; strings]),
+            streak: 0,
+        }
+    }
+
+    ///Finds the string of freq and updates the tuned flags, returns the note against that string
+    pub fn update(&mut self, freq: f32, table: &[Option<f32>]) -> Option<Note> {
+        let (index, note) = self.tuning.nearest_string(freq, table)?;
+        if self.current != Some(index) {
+            self.streak = 0;
+        }
+        self.current = Some(index);
+        if note.in_tune() {
+            self.streak += 1;
+            if self.streak >= TUNED_STREAK {
+                self.set_tuned(index, true);
+            }
+        } else {
+            self.streak = 0;
+            self.set_tuned(index, false);
+        }
+        Some(note)
+    }
+
+    fn set_tuned(&mut self, index: usize, tuned: bool) {
+        if self.tuned[index] != tuned {
+            let mut flags = self.tuned.to_vec();
+            flags[index] = tuned;
+            self.tuned = Arc::from(flags);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.current = None;
+        self.streak = 0;
+        self.tuned = Arc::from(vec![false; self.tuning.strings.len()]);
+    }
+}
@@ -1,72 +1,272 @@
-use audio::audio_bridge::BUFFER_SIZE;
 use rtrb::Consumer;
+use std::sync::Arc;
+pub mod analysis;
+pub mod instrument;
+pub mod loudness;
+pub mod metering;
+pub mod note;
+pub mod pitch;
+pub mod pitch_history;
+pub mod spectrogram;
+pub mod spectrum;
+pub mod trigger;
+pub mod tuning;
 pub mod visualizer;
-pub use visualizer::Visualizer;
+pub mod window;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod worker;
+use analysis::BufferPool;
+pub use analysis::{Analysis, Envelope};
+pub use instrument::{InstrumentTuning, StringTracker};
+pub use loudness::Loudness;
+pub use metering::{Ballistics, Meter, MeterReading};
+pub use note::Note;
+pub use pitch::{PitchAlgorithm, PitchDetector, PitchEstimate};
+pub use pitch_history::{PitchAxis, PitchHistory, PitchPoint};
+pub use spectrogram::{FrequencyAxis, Spectrogram};
+pub use spectrum::{Spectrum, WindowFunction};
+pub use trigger::{Trigger, TriggerMode};
+pub use tuning::{Temperament, TuningSystem};
+pub use visualizer::{Features, Visualizer};
+pub use window::SlidingWindow;
+#[cfg(not(target_arch = "wasm32"))]
+pub use worker::DspWorker;
 
 ///We use this struct to compute on samples and store results ready to be displayed by ui
 pub struct DigitalSignalProcessor {
-    consumer: Consumer<f32>,
+    //None offline, where samples are handed to process() instead
+    consumer: Option<Consumer<f32>>,
     pub rms: f32,
+    ///Largest absolute sample of the window
+    pub peak: f32,
+    ///Runs on every sample, not only on the window of each hop
+    pub meter: Meter,
+    window: SlidingWindow,
+    //what we drained from the ringbuff during the current update
+    incoming: Vec<f32>,
+    //the window as of the last hop, oldest first, what rms, pitch and waveform are computed on
     sample_buffer: Vec<f32>,
+    hops: u64,
+    //samples read from the ringbuff or skipped since the start, and where the last hop ended
+    position: u64,
+    analysed_at: u64,
+    dropped: u64,
+    underruns: u64,
+    //a device keeps recording while we lag, a file or a generator waits for us
+    live: bool,
     pub frequency: Option<f32>,
-    pub note: Option<String>,
+    pub note: Option<Note>,
+    ///How periodic the signal was when the frequency was detected, from 0.0 to 1.0
+    pub clarity: Option<f32>,
     pub sample_rate: f32,
+    a4: f32,
+    tuning: TuningSystem,
+    //target frequency of each MIDI key, rebuilt when a4 or the tuning change
+    note_table: Vec<Option<f32>>,
+    ///When tuning an instrument, notes are compared to its strings instead of the chromatic scale
+    pub strings: Option<StringTracker>,
+    pitch_algorithm: PitchAlgorithm,
+    pitch_detector: Box<dyn PitchDetector>,
+    pub spectrum: Spectrum,
+    pub spectrogram: Spectrogram,
+    //buffers of the snapshots, reused once the ui is done with them
+    sample_pool: BufferPool,
+    spectrum_pool: BufferPool,
 }
 
 //The Audio Callback async rust function or the AudioWorklet will write samples in the ring buf
 //the consumer end allows us to read it
 impl DigitalSignalProcessor {
     pub fn new(consumer: Consumer<f32>) -> Self {
+        Self::with_consumer(Some(consumer))
+    }
+
+    ///A DSP without ringbuff, to analyse a whole file as fast as we can with process()
+    pub fn offline() -> Self {
+        Self::with_consumer(None)
+    }
+
+    fn with_consumer(consumer: Option<Consumer<f32>>) -> Self {
         Self {
             consumer,
-            sample_buffer: Vec::with_capacity(BUFFER_SIZE),
+            window: SlidingWindow::new(4096, 1024),
+            incoming: Vec::new(),
+            sample_buffer: Vec::new(),
+            hops: 0,
+            position: 0,
+            analysed_at: 0,
+            dropped: 0,
+            underruns: 0,
+            live: true,
             rms: 0.0,
+            peak: 0.0,
+            meter: Meter::new(48000.0, Ballistics::Digital),
             frequency: None,
             note: None,
+            clarity: None,
             sample_rate: 48000.0,
+            a4: note::DEFAULT_A4,
+            tuning: TuningSystem::default(),
+            note_table: TuningSystem::default().frequencies(note::DEFAULT_A4),
+            strings: None,
+            pitch_algorithm: PitchAlgorithm::Yin,
+            pitch_detector: PitchAlgorithm::Yin.detector(),
+            spectrum: Spectrum::new(4096, WindowFunction::Hann),
+            spectrogram: Spectrogram::new(256),
+            sample_pool: BufferPool::default(),
+            spectrum_pool: BufferPool::default(),
         }
     }
 
-    //we call this function in the eframe loop
-    //at each frame, we update our sample_buffer so we work on the latests samples
-    pub fn update(&mut self, feature: Visualizer) {
-        self.sample_buffer.clear();
-        let mut count = 0;
-        while let Ok(sample) = self.consumer.pop() {
-            self.sample_buffer.push(sample);
-            count += 1;
-            if self.sample_buffer.len() >= BUFFER_SIZE {
-                break;
-            }
+    //we call this function in the eframe loop, or in a loop of the worker thread on native.
+    //We drain everything the ringbuff holds, so no sample is left behind whatever the frame rate,
+    //and analyse the window each time a hop completes
+    pub fn update(&mut self, features: impl Into<Features>) {
+        self.skip_late();
+        let Some(consumer) = &mut self.consumer else {
+            return;
+        };
+
+        //the buffer is taken out of self for process() to borrow it, and put back for the next update
+        let mut incoming = std::mem::take(&mut self.incoming);
+        incoming.clear();
+        if let Ok(chunk) = consumer.read_chunk(consumer.slots()) {
+            let (first, second) = chunk.as_slices();
+            incoming.extend_from_slice(first);
+            incoming.extend_from_slice(second);
+            chunk.commit_all();
         }
         //faire une macro pour les logs pour egui / cli / wasm
-        if count > 0 {
-            #[cfg(target_arch = "wasm32")]
-            web_sys::console::log_1(&format!("Read {} samples from ringbuffer", count).into());
+        #[cfg(target_arch = "wasm32")]
+        if !incoming.is_empty() {
+            web_sys::console::log_1(
+                &format!("Read {} samples from ringbuffer", incoming.len()).into(),
+            );
         }
-        if self.sample_buffer.is_empty() {
+        if incoming.is_empty() {
             #[cfg(target_arch = "wasm32")]
             web_sys::console::log_1(&"No samples available".into());
-            self.rms = 0.0;
-            self.frequency = None;
-            self.note = None;
+            self.starved();
+            self.incoming = incoming;
+            return;
+        };
+        self.process(&incoming, features);
+        self.incoming = incoming;
+    }
+
+    ///Reads the ringbuff only up to the end of the next hop, and analyses it : a caller that wants
+    ///every analysis instead of the latest one calls it until it returns false, which it does
+    ///when the ringbuff ran out before the hop completed
+    pub fn next_hop(&mut self, features: impl Into<Features>) -> bool {
+        self.skip_late();
+        let Some(consumer) = &mut self.consumer else {
+            return false;
+        };
+        let wanted = self.window.until_hop().min(consumer.slots());
+        if wanted == 0 {
+            return false;
+        }
+        let mut incoming = std::mem::take(&mut self.incoming);
+        incoming.clear();
+        if let Ok(chunk) = consumer.read_chunk(wanted) {
+            let (first, second) = chunk.as_slices();
+            incoming.extend_from_slice(first);
+            incoming.extend_from_slice(second);
+            chunk.commit_all();
+        }
+        let hops = self.hops;
+        self.process(&incoming, features);
+        self.incoming = incoming;
+        self.hops != hops
+    }
+
+    //if we fell behind a live input, what is older than two windows would only add latency :
+    //we skip it. A file or a generator is only ahead of us, each of its samples is analysed
+    fn skip_late(&mut self) {
+        let Some(consumer) = &mut self.consumer else {
             return;
         };
-        //For now we only calculare RMS, but data to display by ui will compute here
+        let pending = consumer.slots();
+        let max_latency = 2 * self.window.size();
+        if self.live && pending > max_latency {
+            let skipped = pending - max_latency;
+            if let Ok(chunk) = consumer.read_chunk(skipped) {
+                chunk.commit_all();
+            }
+            self.dropped += skipped as u64;
+            self.position += skipped as u64;
+            self.meter.discontinuity();
+        }
+    }
+
+    ///Pushes samples in the window and analyses it if a hop completed, what update does with the
+    ///ringbuff. Offline, handing one hop at a time gets every analysis instead of the last one
+    pub fn process(&mut self, samples: &[f32], features: impl Into<Features>) {
+        let features = features.into();
+        self.meter.set_sample_rate(self.sample_rate);
+        self.meter.push(samples);
+        //the spectrum keeps its own history, it gets the same samples in the same hops. Each hop
+        //adds one column to the waterfall, so its speed does not depend on the frame rate
+        let mut hops = 0;
+        let mut offset = 0;
+        while offset < samples.len() {
+            let (taken, complete) = self.window.push(&samples[offset..]);
+            self.spectrum.push(&samples[offset..offset + taken]);
+            offset += taken;
+            self.position += taken as u64;
+            if complete {
+                hops += 1;
+                self.analysed_at = self.position;
+                if features.spectrogram {
+                    self.spectrum.compute();
+                    self.spectrogram.push(self.spectrum.magnitudes_db());
+                }
+            }
+        }
+        if hops == 0 {
+            return;
+        }
+        self.hops += hops;
+
+        //the other results only need the latest window
+        self.sample_buffer.clear();
+        self.sample_buffer.extend_from_slice(self.window.samples());
         let sum: f32 = self.sample_buffer.iter().map(|&s| s * s).sum();
         self.rms = (sum / self.sample_buffer.len() as f32).sqrt();
+        self.peak = self
+            .sample_buffer
+            .iter()
+            .fold(0.0, |peak: f32, &s| peak.max(s.abs()));
+
+        //we only pay for the FFT when it is displayed, the spectrogram already computed this one
+        if features.spectrum && !features.spectrogram {
+            self.spectrum.compute();
+        }
 
-        if feature == Visualizer::Freq {
-            if let Some(freq) = self.autocorrelation(&self.sample_buffer, self.sample_rate) {
+        if features.pitch {
+            if let Some(estimate) = self
+                .pitch_detector
+                .detect(&self.sample_buffer, self.sample_rate)
+            {
+                let freq = estimate.frequency;
                 self.frequency = Some(freq);
-                self.note = Some(Self::freq_to_note(freq));
+                self.clarity = Some(estimate.clarity);
+                let note = match &mut self.strings {
+                    Some(strings) => strings.update(freq, &self.note_table),
+                    None => Note::nearest(freq, &self.note_table),
+                };
+                self.note = note.map(|note| self.named(note));
                 #[cfg(target_arch = "wasm32")]
-                web_sys::console::log_1(
-                    &format!("Detected: {} Hz ({})", freq, Self::freq_to_note(freq)).into(),
-                );
+                if let Some(note) = self.note {
+                    web_sys::console::log_1(
+                        &format!("Detected: {} Hz ({} {:+.1} cents)", freq, note, note.cents)
+                            .into(),
+                    );
+                }
             } else {
                 self.frequency = None;
                 self.note = None;
+                self.clarity = None;
             }
         }
 
@@ -77,96 +277,164 @@ impl DigitalSignalProcessor {
     pub fn get_rms(&self) -> f32 {
         self.rms
     }
+    pub fn get_peak(&self) -> f32 {
+        self.peak
+    }
     pub fn get_samples(&self, count: usize) -> Vec<f32> {
-        if self.sample_buffer.is_empty() {
-            return Vec::new();
-        }
+        analysis::decimate(&self.sample_buffer, count)
+    }
+
+    ///Min and max of the window over count columns, what a waveform should draw
+    pub fn get_envelope(&self, count: usize) -> Vec<Envelope> {
+        analysis::envelope(&self.sample_buffer, count)
+    }
 
-        let buffer_len = self.sample_buffer.len();
+    ///Window analysed at each hop, and the hop, in samples
+    pub fn window_size(&self) -> (usize, usize) {
+        (self.window.size(), self.window.hop())
+    }
 
-        if count >= buffer_len {
-            return self.sample_buffer.clone();
-        }
+    ///Changes the analysis window and hop : the hop is kept under half the window, so
+    ///consecutive analyses overlap. The history restarts from silence
+    pub fn set_window_size(&mut self, size: usize, hop: usize) {
+        self.window.resize(size, hop);
+    }
 
-        let step = buffer_len as f32 / count as f32;
-        let mut samples = Vec::with_capacity(count);
+    ///Whether the ringbuff is fed by a device, which does not wait for the DSP : only then are
+    ///late samples skipped to keep the latency down
+    pub fn set_live(&mut self, live: bool) {
+        self.live = live;
+    }
 
-        for i in 0..count {
-            let index = (i as f32 * step) as usize;
-            if index < buffer_len {
-                samples.push(self.sample_buffer[index]);
-            }
+    ///Analyses run since the start, one per hop
+    pub fn hops(&self) -> u64 {
+        self.hops
+    }
+
+    ///Where the latest analysed window ends in the stream, in seconds since the start
+    pub fn time(&self) -> f64 {
+        self.analysed_at as f64 / self.sample_rate as f64
+    }
+
+    ///Samples skipped because the DSP fell more than two windows behind a live input
+    pub fn dropped_samples(&self) -> u64 {
+        self.dropped
+    }
+
+    ///Updates that found the ringbuff empty, and the ones a worker counted while waiting
+    pub fn underruns(&self) -> u64 {
+        self.underruns
+    }
+
+    ///Counts an underrun and forgets the results, which no sample backs anymore : what update
+    ///does on an empty ringbuff, for a caller that polls the ringbuff itself
+    pub fn starved(&mut self) {
+        self.underruns += 1;
+        self.rms = 0.0;
+        self.peak = 0.0;
+        self.frequency = None;
+        self.note = None;
+        self.clarity = None;
+    }
+
+    ///Samples waiting in the ringbuff
+    pub fn pending_samples(&self) -> usize {
+        self.consumer.as_ref().map_or(0, |c| c.slots())
+    }
+
+    ///Copies the latest results, to hand them to another thread or keep them for a frame
+    pub fn analysis(&mut self) -> Analysis {
+        Analysis {
+            time: self.time(),
+            rms: self.rms,
+            peak: self.peak,
+            frequency: self.frequency,
+            note: self.note,
+            clarity: self.clarity,
+            sample_rate: self.sample_rate,
+            meter: self.meter.reading(),
+            a4: self.a4,
+            pitch_algorithm: self.pitch_algorithm,
+            strings: self.strings.clone(),
+            samples: self.sample_pool.share(&self.sample_buffer),
+            spectrum: self.spectrum_pool.share(self.spectrum.magnitudes_db()),
+            spectrogram: Arc::clone(self.spectrogram.frames()),
+            spectrogram_capacity: self.spectrogram.capacity(),
+            skipped_samples: self.dropped,
+            underruns: self.underruns,
         }
+    }
 
-        samples
+    ///Magnitude in dBFS of each FFT bin, from 0 Hz to Nyquist
+    pub fn get_spectrum(&self) -> &[f32] {
+        self.spectrum.magnitudes_db()
     }
+
     pub fn get_frequency(&self) -> Option<f32> {
         self.frequency
     }
 
-    pub fn get_note(&self) -> Option<String> {
-        self.note.clone()
+    pub fn get_note(&self) -> Option<Note> {
+        self.note
     }
 
-    fn freq_to_note(freq: f32) -> String {
-        let notes = [
-            "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
-        ];
+    pub fn get_clarity(&self) -> Option<f32> {
+        self.clarity
+    }
 
-        let a4 = 440.0;
-        let note_number = 69.0 + 12.0 * (freq / a4).log2();
-        let note_number = note_number.round() as i32;
+    ///Reference frequency of A4 the notes are computed from
+    pub fn a4(&self) -> f32 {
+        self.a4
+    }
 
-        let note_index = note_number.rem_euclid(12);
-        let octave = note_number / 12 - 1;
+    ///Sets the reference pitch, kept within A4_RANGE and rounded to 0.1 Hz
+    pub fn set_a4(&mut self, a4: f32) {
+        let (min, max) = (*note::A4_RANGE.start(), *note::A4_RANGE.end());
+        self.a4 = ((a4 * 10.0).round() / 10.0).clamp(min, max);
+        self.note_table = self.tuning.frequencies(self.a4);
+    }
 
-        format!("{}{}", notes[note_index as usize], octave)
+    ///The note of the current tuning system closest to freq
+    pub fn nearest_note(&self, freq: f32) -> Option<Note> {
+        Note::nearest(freq, &self.note_table).map(|note| self.named(note))
     }
 
-    fn autocorrelation(&self, buffer: &[f32], sample_rate: f32) -> Option<f32> {
-        let size = buffer.len();
-        if size < 1024 {
-            return None;
+    //a note of a scale that has not 12 degrees is named by its degree
+    fn named(&self, note: Note) -> Note {
+        Note {
+            degree: self.tuning.degree(note.midi),
+            ..note
         }
+    }
 
-        // let rms = (buffer.iter().map(|x| x * x).sum::<f32>() / size as f32).sqrt();
-        // if rms < 0.01 {
-        //     return None;
-        // }
+    pub fn tuning(&self) -> &TuningSystem {
+        &self.tuning
+    }
 
-        let mean = buffer.iter().sum::<f32>() / size as f32;
-        let mut signal = Vec::with_capacity(size);
-        for &x in buffer {
-            signal.push(x - mean);
-        }
+    ///Notes are now found against this tuning system instead of equal temperament
+    pub fn set_tuning(&mut self, tuning: TuningSystem) {
+        self.note_table = tuning.frequencies(self.a4);
+        self.tuning = tuning;
+    }
 
-        let mut corr = vec![0.0; size];
-        for lag in 0..size {
-            let mut sum = 0.0;
-            for i in 0..(size - lag) {
-                sum += signal[i] * signal[i + lag];
-            }
-            corr[lag] = sum;
-        }
+    pub fn instrument(&self) -> Option<&InstrumentTuning> {
+        self.strings.as_ref().map(|s| &*s.tuning)
+    }
 
-        let mut d = 0;
-        while d + 1 < size && corr[d] > corr[d + 1] {
-            d += 1;
-        }
+    ///Tunes an instrument string by string, or goes back to the chromatic tuner with None
+    pub fn set_instrument(&mut self, instrument: Option<InstrumentTuning>) {
+        self.strings = instrument.map(StringTracker::new);
+    }
 
-        let mut max_pos = d;
-        let mut max_val = corr[d];
-        for (i, c) in corr.iter_mut().enumerate().take(size).skip(d) {
-            if *c > max_val {
-                max_val = *c;
-                max_pos = i;
-            }
-        }
+    pub fn pitch_algorithm(&self) -> PitchAlgorithm {
+        self.pitch_algorithm
+    }
 
-        if max_pos == 0 {
-            return None;
+    ///Swaps the pitch detector, the next update already uses the new one
+    pub fn set_pitch_algorithm(&mut self, algorithm: PitchAlgorithm) {
+        if algorithm != self.pitch_algorithm {
+            self.pitch_algorithm = algorithm;
+            self.pitch_detector = algorithm.detector();
         }
-
-        Some(sample_rate / max_pos as f32)
     }
 }
@@ -0,0 +1,199 @@
+use std::f64::consts::PI;
+
+//ITU-R BS.1770 : 400 ms blocks overlapping by 75%, so a new block every 100 ms
+const SUB_BLOCKS: usize = 4;
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+//Direct form I second order filter
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[0] * self.y[0]
+            - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+///Integrated loudness in LUFS, K-weighted and gated as BS.1770 asks. Each channel is weighted
+///on its own and their powers are summed, so a stereo file is louder than either of its channels
+pub struct Loudness {
+    //the pre-filter and the high pass of each channel
+    filters: Vec<(Biquad, Biquad)>,
+    weights: Vec<f64>,
+    sub_block_size: usize,
+    //weighted power of the frames of the sub block being filled
+    sum: f64,
+    count: usize,
+    sub_blocks: Vec<f64>,
+    //mean power of each complete 400 ms block
+    blocks: Vec<f64>,
+}
+
+impl Loudness {
+    pub fn new(sample_rate: f32, channels: usize) -> Self {
+        let fs = sample_rate as f64;
+
+        //the pre-filter, a high shelf modelling the head, coefficients computed for any rate
+        let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (PI * f0 / fs).tan();
+        let vh = 10f64.powf(gain / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        //then the RLB high pass
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (PI * f0 / fs).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let channels = channels.max(1);
+        Self {
+            filters: vec![(shelf, high_pass); channels],
+            weights: (0..channels).map(|c| weight(c, channels)).collect(),
+            sub_block_size: ((fs * 0.1).round() as usize).max(1),
+            sum: 0.0,
+            count: 0,
+            sub_blocks: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    ///Takes interleaved frames, one sample per channel
+    pub fn push(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.filters.len()) {
+            for ((&s, (shelf, high_pass)), weight) in
+                frame.iter().zip(&mut self.filters).zip(&self.weights)
+            {
+                let weighted = high_pass.process(shelf.process(s as f64));
+                self.sum += weight * weighted * weighted;
+            }
+            self.count += 1;
+            if self.count == self.sub_block_size {
+                self.sub_blocks.push(self.sum / self.count as f64);
+                self.sum = 0.0;
+                self.count = 0;
+                if self.sub_blocks.len() >= SUB_BLOCKS {
+                    let last = &self.sub_blocks[self.sub_blocks.len() - SUB_BLOCKS..];
+                    self.blocks
+                        .push(last.iter().sum::<f64>() / SUB_BLOCKS as f64);
+                }
+            }
+        }
+    }
+
+    ///None while less than 400 ms were pushed, or when everything is under the -70 LUFS gate
+    pub fn integrated(&self) -> Option<f32> {
+        let above = |gate: f64| -> Vec<f64> {
+            self.blocks
+                .iter()
+                .copied()
+                .filter(|&power| to_lufs(power) > gate)
+                .collect()
+        };
+        let absolute = above(ABSOLUTE_GATE);
+        if absolute.is_empty() {
+            return None;
+        }
+        let relative_gate = to_lufs(mean(&absolute)) + RELATIVE_GATE;
+        let gated = above(relative_gate.max(ABSOLUTE_GATE));
+        if gated.is_empty() {
+            return None;
+        }
+        Some(to_lufs(mean(&gated)) as f32)
+    }
+}
+
+//BS.1770 weights the surrounds of a 5.1 file (L R C LFE Ls Rs in a WAV) by 1.41 and ignores
+//the LFE, every other channel counts as a front one
+fn weight(channel: usize, channels: usize) -> f64 {
+    match (channels, channel) {
+        (6, 3) => 0.0,
+        (6, 4 | 5) => 1.41,
+        _ => 1.0,
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.max(1e-20).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //BS.1770 : a 0 dBFS 997 Hz sine on one channel reads -3.01 LUFS
+    #[test]
+    fn full_scale_sine() {
+        for sample_rate in [44100.0, 48000.0] {
+            let mut loudness = Loudness::new(sample_rate, 1);
+            let samples: Vec<f32> = (0..(5.0 * sample_rate) as usize)
+                .map(|i| (std::f32::consts::TAU * 997.0 * i as f32 / sample_rate).sin())
+                .collect();
+            loudness.push(&samples);
+            let lufs = loudness.integrated().expect("the sine is over the gates");
+            assert!(
+                (lufs + 3.01).abs() < 0.1,
+                "{} LUFS at {} Hz",
+                lufs,
+                sample_rate
+            );
+        }
+    }
+
+    //the same sine on both channels of a stereo file is 3 dB louder
+    #[test]
+    fn channels_are_summed() {
+        let mut loudness = Loudness::new(48000.0, 2);
+        let samples: Vec<f32> = (0..48000 * 5)
+            .flat_map(|i| {
+                let s = (std::f32::consts::TAU * 997.0 * i as f32 / 48000.0).sin();
+                [s, s]
+            })
+            .collect();
+        loudness.push(&samples);
+        let lufs = loudness.integrated().expect("the sine is over the gates");
+        assert!(lufs.abs() < 0.1, "{} LUFS", lufs);
+    }
+
+    #[test]
+    fn silence_is_gated() {
+        let mut loudness = Loudness::new(48000.0, 1);
+        loudness.push(&vec![0.0; 48000 * 2]);
+        assert_eq!(loudness.integrated(), None);
+    }
+}
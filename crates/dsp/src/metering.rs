@@ -0,0 +1,405 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+
+//the true peak is read on the signal upsampled 4 times, by a polyphase FIR of 12 taps per phase
+const OVERSAMPLING: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+//the peak hold marker stays this long on the highest peak before following the signal again
+const HOLD_SECONDS: f32 = 2.0;
+//RMS is averaged over about this long, the integration time of a VU
+const RMS_SECONDS: f32 = 0.3;
+//a VU is calibrated on sines : their rectified mean times this reads their RMS
+const VU_CALIBRATION: f32 = std::f32::consts::PI / (2.0 * std::f32::consts::SQRT_2);
+//a sample this loud clipped : integer devices top out one LSB of 16 bits under 1.0, as cpal
+//converts their full scale to 32767 / 32768
+const CLIP: f32 = 1.0 - 1.0 / 32768.0;
+//a true peak over this is an intersample over, the margin is the ripple of the interpolation
+const OVER: f32 = 1.001;
+//quieter than this is -inf for a meter, and JSON can't hold -inf
+pub const FLOOR_DB: f32 = -120.0;
+
+///How the needle (or the bar) follows the signal
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Serialize, Deserialize)]
+pub enum Ballistics {
+    ///Sample peak, instant attack, falls 20 dB in 1.7 s
+    Digital,
+    ///Average responding, 300 ms up and down, calibrated to read the RMS of a sine
+    Vu,
+    ///Quasi peak, 5 ms integration, falls 20 dB in 1.5 s (DIN 45406)
+    #[value(name = "ppm1")]
+    PpmTypeI,
+    ///Quasi peak, 10 ms integration, falls 24 dB in 2.8 s (BBC / EBU)
+    #[value(name = "ppm2")]
+    PpmTypeII,
+}
+
+impl fmt::Display for Ballistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Ballistics::Digital => "Digital",
+            Ballistics::Vu => "VU",
+            Ballistics::PpmTypeI => "PPM I",
+            Ballistics::PpmTypeII => "PPM II",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Ballistics {
+    pub const ALL: [Ballistics; 4] = [
+        Ballistics::Digital,
+        Ballistics::Vu,
+        Ballistics::PpmTypeI,
+        Ballistics::PpmTypeII,
+    ];
+
+    //attack time constant in seconds (0 is instant), and fall in dB per second
+    fn timing(self) -> (f32, f32) {
+        match self {
+            Ballistics::Digital => (0.0, 20.0 / 1.7),
+            Ballistics::Vu => (0.065, 0.0),
+            Ballistics::PpmTypeI => (0.0017, 20.0 / 1.5),
+            Ballistics::PpmTypeII => (0.0034, 24.0 / 2.8),
+        }
+    }
+
+    ///dBFS the zero of the scale is aligned on : 0 VU, PPM 4, or the top of a digital meter
+    pub fn reference_db(self) -> f32 {
+        match self {
+            Ballistics::Digital => 0.0,
+            Ballistics::Vu => -18.0,
+            Ballistics::PpmTypeI => -9.0,
+            Ballistics::PpmTypeII => -18.0,
+        }
+    }
+}
+
+///What the meter shows, in dBFS, floored at FLOOR_DB
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterReading {
+    ///Highest sample of the last block
+    pub peak_db: f32,
+    ///Highest point of the last block between the samples too, what a DAC will output
+    pub true_peak_db: f32,
+    ///RMS over the last 300 ms : a full scale sine reads -3 dBFS
+    pub rms_db: f32,
+    ///The level with the ballistics applied, what the bar shows
+    pub level_db: f32,
+    ///Highest true peak of the last 2 seconds
+    pub hold_db: f32,
+    ///Samples at full scale (within one LSB of 16 bits) or over, or blocks whose true peak went
+    ///over, since the start. A frontend latches its clip light by remembering the count it last
+    ///acknowledged
+    pub clips: u64,
+}
+
+impl Default for MeterReading {
+    fn default() -> Self {
+        Self {
+            peak_db: FLOOR_DB,
+            true_peak_db: FLOOR_DB,
+            rms_db: FLOOR_DB,
+            level_db: FLOOR_DB,
+            hold_db: FLOOR_DB,
+            clips: 0,
+        }
+    }
+}
+
+///Sample peak, 4x oversampled true peak, RMS, peak hold, clip count, and one of the standard
+///ballistics, over every sample pushed. Cheap enough to run on every block of the stream
+pub struct Meter {
+    sample_rate: f32,
+    ballistics: Ballistics,
+    //polyphase interpolation filter, phase by phase, and the latest input samples, newest first
+    phases: [[f32; TAPS_PER_PHASE]; OVERSAMPLING],
+    history: [f32; TAPS_PER_PHASE],
+    //samples still to come before the history is filled and the interpolation means something
+    warmup: usize,
+    attack: f32,
+    release: f32,
+    rms_coefficient: f32,
+    //the states, all linear
+    level: f32,
+    mean_square: f32,
+    hold: f32,
+    hold_left: usize,
+    peak: f32,
+    true_peak: f32,
+    clips: u64,
+}
+
+impl Meter {
+    pub fn new(sample_rate: f32, ballistics: Ballistics) -> Self {
+        let mut meter = Self {
+            sample_rate,
+            ballistics,
+            phases: interpolation_phases(),
+            history: [0.0; TAPS_PER_PHASE],
+            warmup: TAPS_PER_PHASE,
+            attack: 1.0,
+            release: 1.0,
+            rms_coefficient: 1.0,
+            level: 0.0,
+            mean_square: 0.0,
+            hold: 0.0,
+            hold_left: 0,
+            peak: 0.0,
+            true_peak: 0.0,
+            clips: 0,
+        };
+        meter.update_coefficients();
+        meter
+    }
+
+    fn update_coefficients(&mut self) {
+        let rate = self.sample_rate.max(1.0);
+        let (attack, fall_db) = self.ballistics.timing();
+        //a one pole filter reaching 1 - 1/e after time seconds
+        let one_pole = |time: f32| {
+            if time <= 0.0 {
+                1.0
+            } else {
+                1.0 - (-1.0 / (time * rate)).exp()
+            }
+        };
+        self.attack = one_pole(attack);
+        //the peak meters fall linearly in dB, the VU falls like it rises and has no release
+        self.release = 10f32.powf(-fall_db / 20.0 / rate);
+        self.rms_coefficient = one_pole(RMS_SECONDS / 4.6);
+    }
+
+    pub fn ballistics(&self) -> Ballistics {
+        self.ballistics
+    }
+
+    pub fn set_ballistics(&mut self, ballistics: Ballistics) {
+        self.ballistics = ballistics;
+        self.update_coefficients();
+    }
+
+    ///The time constants depend on it, changing it keeps the readings
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        if sample_rate != self.sample_rate {
+            self.sample_rate = sample_rate;
+            self.update_coefficients();
+        }
+    }
+
+    ///The next samples do not follow the last ones, samples were skipped : the interpolation
+    ///would ring on the jump and read an over that no converter will output
+    pub fn discontinuity(&mut self) {
+        self.warmup = TAPS_PER_PHASE;
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+        self.peak = 0.0;
+        self.true_peak = 0.0;
+        let vu = self.ballistics == Ballistics::Vu;
+        for &s in samples {
+            let magnitude = s.abs();
+            self.peak = self.peak.max(magnitude);
+            if magnitude >= CLIP {
+                self.clips += 1;
+            }
+
+            self.history.copy_within(..TAPS_PER_PHASE - 1, 1);
+            self.history[0] = s;
+            if self.warmup > 0 {
+                self.warmup -= 1;
+            } else {
+                for phase in &self.phases {
+                    let value: f32 = phase.iter().zip(&self.history).map(|(h, x)| h * x).sum();
+                    self.true_peak = self.true_peak.max(value.abs());
+                }
+            }
+
+            self.mean_square += (s * s - self.mean_square) * self.rms_coefficient;
+
+            if vu {
+                self.level += (magnitude * VU_CALIBRATION - self.level) * self.attack;
+            } else if magnitude > self.level {
+                self.level += (magnitude - self.level) * self.attack;
+            } else {
+                self.level *= self.release;
+            }
+        }
+        //the filter delays the signal, the sample peak still counts for the true peak
+        self.true_peak = self.true_peak.max(self.peak);
+        if self.true_peak > OVER && self.peak < CLIP {
+            //an intersample over : no sample clipped, but the converter will
+            self.clips += 1;
+        }
+
+        if self.true_peak >= self.hold || self.hold_left == 0 {
+            self.hold = self.true_peak;
+            self.hold_left = (HOLD_SECONDS * self.sample_rate) as usize;
+        } else {
+            self.hold_left = self.hold_left.saturating_sub(samples.len());
+        }
+    }
+
+    pub fn reading(&self) -> MeterReading {
+        MeterReading {
+            peak_db: to_db(self.peak),
+            true_peak_db: to_db(self.true_peak),
+            rms_db: to_db(self.mean_square.sqrt()),
+            level_db: to_db(self.level),
+            hold_db: to_db(self.hold),
+            clips: self.clips,
+        }
+    }
+
+    ///Back to silence, the clip count included
+    pub fn reset(&mut self) {
+        *self = Meter::new(self.sample_rate, self.ballistics);
+    }
+}
+
+///dBFS of a linear amplitude, silence is floored at FLOOR_DB instead of going to -inf
+pub fn to_db(amplitude: f32) -> f32 {
+    (20.0 * amplitude.log10()).max(FLOOR_DB)
+}
+
+//A windowed sinc cut at the original Nyquist, split in its 4 phases : phase p computes the
+//point p / 4 of a sample after the centre of the history. Each phase is normalised to a gain
+//of 1, so a DC of full scale reads 0 dBTP
+fn interpolation_phases() -> [[f32; TAPS_PER_PHASE]; OVERSAMPLING] {
+    let length = OVERSAMPLING * TAPS_PER_PHASE;
+    let centre = (length - 1) as f64 / 2.0;
+    let mut phases = [[0.0; TAPS_PER_PHASE]; OVERSAMPLING];
+    for (p, phase) in phases.iter_mut().enumerate() {
+        for (k, tap) in phase.iter_mut().enumerate() {
+            let n = (k * OVERSAMPLING + p) as f64;
+            let x = (n - centre) / OVERSAMPLING as f64;
+            let sinc = if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            };
+            //Blackman window over the whole filter
+            let t = n / (length - 1) as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * t).cos() + 0.08 * (4.0 * PI * t).cos();
+            *tap = (sinc * window) as f32;
+        }
+        let sum: f32 = phase.iter().sum();
+        phase.iter_mut().for_each(|tap| *tap /= sum);
+    }
+    phases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    //pushed like an audio callback would, 10 ms at a time
+    const BLOCK: usize = 480;
+
+    fn sine(frequency: f32, amplitude: f32, phase: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE) as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE;
+                amplitude * (std::f32::consts::TAU * frequency * t + phase).sin()
+            })
+            .collect()
+    }
+
+    fn push(meter: &mut Meter, samples: &[f32]) {
+        for block in samples.chunks(BLOCK) {
+            meter.push(block);
+        }
+    }
+
+    fn assert_db(db: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (db - expected).abs() <= tolerance,
+            "read {} dB instead of {}",
+            db,
+            expected
+        );
+    }
+
+    //at a quarter of the sample rate and 45°, every sample misses the top of the sine by 3 dB
+    #[test]
+    fn true_peak_between_samples() {
+        let mut meter = Meter::new(SAMPLE_RATE, Ballistics::Digital);
+        push(
+            &mut meter,
+            &sine(SAMPLE_RATE / 4.0, 0.5, 0.25 * PI as f32, 0.1),
+        );
+        let reading = meter.reading();
+        assert_db(reading.peak_db, -9.03, 0.05);
+        assert_db(reading.true_peak_db, -6.02, 0.2);
+        assert_eq!(reading.clips, 0);
+    }
+
+    #[test]
+    fn full_scale_sine_rms() {
+        let mut meter = Meter::new(SAMPLE_RATE, Ballistics::Digital);
+        push(&mut meter, &sine(1000.0, 1.0, 0.0, 1.0));
+        assert_db(meter.reading().rms_db, -3.01, 0.05);
+    }
+
+    #[test]
+    fn clips_are_counted() {
+        let mut meter = Meter::new(SAMPLE_RATE, Ballistics::Digital);
+        meter.push(&[0.5, 0.99, -0.99]);
+        assert_eq!(meter.reading().clips, 0);
+        meter.push(&[1.0, 0.0, -1.0, CLIP]);
+        assert_eq!(meter.reading().clips, 3);
+        //no sample reaches full scale, but the converter will go over between them
+        let mut meter = Meter::new(SAMPLE_RATE, Ballistics::Digital);
+        let over = sine(SAMPLE_RATE / 4.0, 1.1, 0.25 * PI as f32, 0.1);
+        push(&mut meter, &over);
+        assert_eq!(meter.reading().clips, (over.len() / BLOCK) as u64);
+    }
+
+    #[test]
+    fn digital_attack_and_release() {
+        let mut meter = Meter::new(SAMPLE_RATE, Ballistics::Digital);
+        meter.push(&[0.5]);
+        assert_db(meter.reading().level_db, -6.02, 0.01);
+        //then falls 20 dB in 1.7 s
+        push(&mut meter, &vec![0.0; (1.7 * SAMPLE_RATE) as usize]);
+        assert_db(meter.reading().level_db, -26.02, 0.1);
+    }
+
+    #[test]
+    fn ppm_release() {
+        let mut meter = Meter::new(SAMPLE_RATE, Ballistics::PpmTypeII);
+        push(&mut meter, &vec![0.5; BLOCK * 10]);
+        assert_db(meter.reading().level_db, -6.02, 0.01);
+        //24 dB in 2.8 s
+        push(&mut meter, &vec![0.0; (2.8 * SAMPLE_RATE) as usize]);
+        assert_db(meter.reading().level_db, -30.02, 0.1);
+    }
+
+    //a VU reaches 99% of a sine in 300 ms, and reads its RMS
+    #[test]
+    fn vu_attack() {
+        let mut meter = Meter::new(SAMPLE_RATE, Ballistics::Vu);
+        let rms = to_db(0.5 / std::f32::consts::SQRT_2);
+        push(&mut meter, &sine(1000.0, 0.5, 0.0, 0.3));
+        assert_db(meter.reading().level_db, rms + to_db(0.99), 0.1);
+        push(&mut meter, &sine(1000.0, 0.5, 0.0, 1.0));
+        assert_db(meter.reading().level_db, rms, 0.1);
+    }
+
+    #[test]
+    fn peak_hold() {
+        //a sine holding its peaks on samples, so the true peak reads the same
+        let mut meter = Meter::new(SAMPLE_RATE, Ballistics::Digital);
+        push(&mut meter, &sine(1000.0, 0.5, 0.0, 0.01));
+        push(&mut meter, &sine(1000.0, 0.1, 0.0, 1.0));
+        assert_db(meter.reading().hold_db, -6.02, 0.05);
+        //once HOLD_SECONDS went by, it follows the signal again
+        push(&mut meter, &sine(1000.0, 0.1, 0.0, 1.01));
+        assert_db(meter.reading().hold_db, -20.0, 0.05);
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+pub const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+pub const DEFAULT_A4: f32 = 440.0;
+
+///Reference pitches we accept, from low baroque tunings to the brightest orchestras
+pub const A4_RANGE: RangeInclusive<f32> = 400.0..=480.0;
+
+///Within this many cents from the target, we consider the note in tune
+pub const IN_TUNE_CENTS: f32 = 5.0;
+
+///The nearest note of a frequency, and how far we are from it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Note {
+    pub name: &'static str,
+    pub octave: i32,
+    ///MIDI note number, A4 is 69
+    pub midi: i32,
+    pub target_frequency: f32,
+    ///Deviation from target_frequency, from -50 (flat) to +50 (sharp)
+    pub cents: f32,
+    ///Degree of a Scala scale that does not have 12 notes : the 12 names mean nothing there,
+    ///the note is named by its degree instead
+    pub degree: Option<usize>,
+}
+
+impl Note {
+    ///Nearest note of freq, with a4 as reference pitch
+    pub fn from_frequency(freq: f32, a4: f32) -> Option<Self> {
+        if !freq.is_finite() || freq <= 0.0 || a4 <= 0.0 {
+            return None;
+        }
+        let midi = (69.0 + 12.0 * (freq / a4).log2()).round() as i32;
+        Self::against(freq, midi, a4 * 2f32.powf((midi - 69) as f32 / 12.0))
+    }
+
+    ///Nearest key of a tuning table, as built by TuningSystem::frequencies, indexed by MIDI number
+    pub fn nearest(freq: f32, table: &[Option<f32>]) -> Option<Self> {
+        if !freq.is_finite() || freq <= 0.0 {
+            return None;
+        }
+        table
+            .iter()
+            .enumerate()
+            .filter_map(|(midi, target)| Self::against(freq, midi as i32, (*target)?))
+            .min_by(|a, b| a.cents.abs().total_cmp(&b.cents.abs()))
+    }
+
+    ///freq compared to the given MIDI note, whatever the distance
+    pub fn against(freq: f32, midi: i32, target_frequency: f32) -> Option<Self> {
+        if !freq.is_finite() || freq <= 0.0 || target_frequency <= 0.0 {
+            return None;
+        }
+        Some(Self {
+            name: NOTE_NAMES[midi.rem_euclid(12) as usize],
+            octave: midi.div_euclid(12) - 1,
+            midi,
+            target_frequency,
+            cents: 1200.0 * (freq / target_frequency).log2(),
+            degree: None,
+        })
+    }
+
+    pub fn in_tune(&self) -> bool {
+        self.cents.abs() <= IN_TUNE_CENTS
+    }
+}
+
+///Name of a MIDI note, like A4 or C#2
+pub fn midi_name(midi: i32) -> String {
+    format!(
+        "{}{}",
+        NOTE_NAMES[midi.rem_euclid(12) as usize],
+        midi.div_euclid(12) - 1
+    )
+}
+
+///Parses a note with its octave, like E2, F#3 or Bb-1, into its MIDI number
+pub fn parse_note(s: &str) -> Result<i32, String> {
+    let s = s.trim();
+    let split = s
+        .char_indices()
+        .skip(1)
+        .find(|(_, c)| c.is_ascii_digit() || *c == '-')
+        .map(|(i, _)| i)
+        .ok_or(format!(
+            "Invalid note \"{}\": expected a note and an octave like E2",
+            s
+        ))?;
+    let (name, octave) = s.split_at(split);
+    let pitch_class = crate::tuning::parse_key(name)? as i32;
+    let octave: i32 = octave
+        .parse()
+        .map_err(|_| format!("Invalid octave in \"{}\"", s))?;
+    let midi = (octave + 1) * 12 + pitch_class;
+    if !(0..128).contains(&midi) {
+        return Err(format!("Note \"{}\" is out of the MIDI range", s));
+    }
+    Ok(midi)
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.degree {
+            Some(degree) => write!(f, "deg {}", degree),
+            None => write!(f, "{}{}", self.name, self.octave),
+        }
+    }
+}
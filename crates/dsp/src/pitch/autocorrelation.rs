@@ -0,0 +1,80 @@
+use super::{MIN_CLARITY, PitchDetector, PitchEstimate};
+
+///The first algorithm of this project : O(n²) time domain autocorrelation, then the highest
+///peak after the first dip. It has no sub-sample precision and often jumps an octave,
+///we keep it to compare the other detectors against
+#[derive(Default)]
+pub struct Autocorrelation;
+
+impl PitchDetector for Autocorrelation {
+    fn detect(&mut self, buffer: &[f32], sample_rate: f32) -> Option<PitchEstimate> {
+        let size = buffer.len();
+        if size < 1024 {
+            return None;
+        }
+
+        let mean = buffer.iter().sum::<f32>() / size as f32;
+        let mut signal = Vec::with_capacity(size);
+        for &x in buffer {
+            signal.push(x - mean);
+        }
+
+        let mut corr = vec![0.0; size];
+        for lag in 0..size {
+            let mut sum = 0.0;
+            for i in 0..(size - lag) {
+                sum += signal[i] * signal[i + lag];
+            }
+            corr[lag] = sum;
+        }
+
+        let mut d = 0;
+        while d + 1 < size && corr[d] > corr[d + 1] {
+            d += 1;
+        }
+
+        let mut max_pos = d;
+        let mut max_val = corr[d];
+        for (i, c) in corr.iter_mut().enumerate().take(size).skip(d) {
+            if *c > max_val {
+                max_val = *c;
+                max_pos = i;
+            }
+        }
+
+        if max_pos == 0 || corr[0] <= 0.0 {
+            return None;
+        }
+        let clarity = (max_val / corr[0]).clamp(0.0, 1.0);
+        if clarity < MIN_CLARITY {
+            return None;
+        }
+
+        Some(PitchEstimate {
+            frequency: sample_rate / max_pos as f32,
+            clarity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::signals::*;
+
+    //without sub-sample precision it is only exact where a period spans many samples, and it
+    //jumps octaves near the top of the range : the other detectors are tested over all of it
+    #[test]
+    fn middle_of_the_range() {
+        for frequency in [110.0, 440.0, 1000.0] {
+            assert_pitch(&mut Autocorrelation, &sine(frequency), frequency, 2.0);
+            assert_pitch(&mut Autocorrelation, &saw(frequency), frequency, 2.0);
+        }
+    }
+
+    #[test]
+    fn no_pitch_in_silence_or_noise() {
+        assert_eq!(Autocorrelation.detect(&silence(), SAMPLE_RATE), None);
+        assert_eq!(Autocorrelation.detect(&noise(), SAMPLE_RATE), None);
+    }
+}
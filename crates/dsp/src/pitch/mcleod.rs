@@ -0,0 +1,126 @@
+use super::{
+    MAX_FREQUENCY, MIN_CLARITY, MIN_FREQUENCY, PitchDetector, PitchEstimate, cross_correlation,
+    parabolic_interpolation,
+};
+use rustfft::FftPlanner;
+
+///McLeod Pitch Method (McLeod & Wyvill, 2005) : peaks of the normalized square difference
+///function are compared to the highest one, the first peak close enough (cutoff) is the period.
+///The height of that peak is directly the clarity
+pub struct McLeod {
+    pub cutoff: f32,
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    planner: FftPlanner<f32>,
+}
+
+impl Default for McLeod {
+    fn default() -> Self {
+        Self {
+            cutoff: 0.93,
+            min_frequency: MIN_FREQUENCY,
+            max_frequency: MAX_FREQUENCY,
+            planner: FftPlanner::new(),
+        }
+    }
+}
+
+impl PitchDetector for McLeod {
+    fn detect(&mut self, buffer: &[f32], sample_rate: f32) -> Option<PitchEstimate> {
+        let size = buffer.len();
+        let min_tau = ((sample_rate / self.max_frequency) as usize).max(1);
+        let max_tau = ((sample_rate / self.min_frequency) as usize).min(size / 2);
+        if max_tau <= min_tau + 1 {
+            return None;
+        }
+
+        //nsdf(tau) = 2 r(tau) / m(tau), m being the energy of both overlapping parts
+        let r = cross_correlation(&mut self.planner, buffer, buffer, max_tau + 1);
+        let mut m = 2.0 * buffer.iter().map(|&x| x * x).sum::<f32>();
+        if m <= f32::EPSILON {
+            return None;
+        }
+        let mut nsdf = vec![0.0f32; max_tau + 1];
+        for tau in 0..=max_tau {
+            if tau > 0 {
+                m -= buffer[tau - 1] * buffer[tau - 1] + buffer[size - tau] * buffer[size - tau];
+            }
+            nsdf[tau] = if m > f32::EPSILON {
+                2.0 * r[tau] / m
+            } else {
+                0.0
+            };
+        }
+
+        //key maxima : the highest point of each positive lobe, after the lobe around lag 0
+        let mut key_maxima: Vec<usize> = Vec::new();
+        let mut tau = 1;
+        while tau < max_tau && nsdf[tau] > 0.0 {
+            tau += 1;
+        }
+        let mut best: Option<usize> = None;
+        while tau < max_tau {
+            if nsdf[tau] > 0.0 {
+                if best.is_none_or(|b| nsdf[tau] > nsdf[b]) {
+                    best = Some(tau);
+                }
+            } else if let Some(b) = best.take() {
+                key_maxima.push(b);
+            }
+            tau += 1;
+        }
+        if let Some(b) = best {
+            key_maxima.push(b);
+        }
+        //the last lobe may be cut by max_tau while still rising : that is no peak
+        key_maxima.retain(|&tau| tau >= min_tau && nsdf[tau] >= nsdf[tau + 1]);
+
+        //peaks are compared at their interpolated top : a short period falls between two lags, and
+        //sampled at a lag its peak could look lower than the one of twice the period
+        let peaks: Vec<(f32, f32)> = key_maxima
+            .iter()
+            .map(|&tau| parabolic_interpolation(&nsdf, tau))
+            .collect();
+        let highest = peaks
+            .iter()
+            .map(|&(_, value)| value)
+            .fold(f32::MIN, f32::max);
+        let threshold = self.cutoff * highest;
+        let &(period, clarity) = peaks.iter().find(|&&(_, value)| value >= threshold)?;
+        if period <= 0.0 || clarity < MIN_CLARITY {
+            return None;
+        }
+        Some(PitchEstimate {
+            frequency: sample_rate / period,
+            clarity: clarity.clamp(0.0, 1.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::signals::*;
+
+    #[test]
+    fn sines_across_the_range() {
+        for frequency in [LOWEST, 41.2, 440.0, 2093.0, HIGHEST] {
+            assert_pitch(&mut McLeod::default(), &sine(frequency), frequency, 3.0);
+        }
+    }
+
+    //the harmonics of a saw must not be taken for the fundamental, nor its octave below
+    #[test]
+    fn saws_across_the_range() {
+        for frequency in [LOWEST, 41.2, 440.0, 2093.0, HIGHEST] {
+            assert_pitch(&mut McLeod::default(), &saw(frequency), frequency, 7.0);
+        }
+    }
+
+    #[test]
+    fn no_pitch_in_silence_or_noise() {
+        let mut detector = McLeod::default();
+        assert_eq!(detector.detect(&silence(), SAMPLE_RATE), None);
+        assert_eq!(detector.detect(&noise(), SAMPLE_RATE), None);
+    }
+}
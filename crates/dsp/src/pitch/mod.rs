@@ -0,0 +1,182 @@
+use clap::ValueEnum;
+use rustfft::FftPlanner;
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub mod autocorrelation;
+pub mod mcleod;
+pub mod yin;
+
+pub use autocorrelation::Autocorrelation;
+pub use mcleod::McLeod;
+pub use yin::Yin;
+
+//Lowest B of a 5 string bass is 30.9 Hz, highest C of a piano 4186 Hz
+pub const MIN_FREQUENCY: f32 = 30.0;
+pub const MAX_FREQUENCY: f32 = 4200.0;
+
+//noise correlates a little with itself at every lag : under this clarity, there is no pitch
+const MIN_CLARITY: f32 = 0.5;
+
+///What a detector found : the fundamental and how periodic the signal looked, from 0.0 (noise)
+///to 1.0 (perfectly periodic)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchEstimate {
+    pub frequency: f32,
+    pub clarity: f32,
+}
+
+///Like AudioBackend for the audio, this trait lets the DSP swap its pitch algorithm at runtime
+pub trait PitchDetector: Send {
+    fn detect(&mut self, buffer: &[f32], sample_rate: f32) -> Option<PitchEstimate>;
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Serialize, Deserialize)]
+pub enum PitchAlgorithm {
+    ///The historical time domain autocorrelation, kept as a baseline
+    Autocorrelation,
+    Yin,
+    Mcleod,
+}
+
+impl fmt::Display for PitchAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PitchAlgorithm::Autocorrelation => "Autocorrelation",
+            PitchAlgorithm::Yin => "YIN",
+            PitchAlgorithm::Mcleod => "McLeod",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PitchAlgorithm {
+    pub const ALL: [PitchAlgorithm; 3] = [
+        PitchAlgorithm::Autocorrelation,
+        PitchAlgorithm::Yin,
+        PitchAlgorithm::Mcleod,
+    ];
+
+    pub fn detector(self) -> Box<dyn PitchDetector> {
+        match self {
+            PitchAlgorithm::Autocorrelation => Box::new(Autocorrelation),
+            PitchAlgorithm::Yin => Box::new(Yin::default()),
+            PitchAlgorithm::Mcleod => Box::new(McLeod::default()),
+        }
+    }
+}
+
+///Computes r[lag] = sum of a[j] * b[j + lag] for lag in 0..lags, in O(n log n) with an FFT
+///instead of the O(n²) double loop
+pub(crate) fn cross_correlation(
+    planner: &mut FftPlanner<f32>,
+    a: &[f32],
+    b: &[f32],
+    lags: usize,
+) -> Vec<f32> {
+    let size = (a.len() + b.len()).next_power_of_two();
+    let fft = planner.plan_fft_forward(size);
+    let ifft = planner.plan_fft_inverse(size);
+
+    let mut fa: Vec<Complex<f32>> = a.iter().map(|&x| Complex::new(x, 0.0)).collect();
+    fa.resize(size, Complex::default());
+    let mut fb: Vec<Complex<f32>> = b.iter().map(|&x| Complex::new(x, 0.0)).collect();
+    fb.resize(size, Complex::default());
+    fft.process(&mut fa);
+    fft.process(&mut fb);
+
+    for (x, y) in fa.iter_mut().zip(fb.iter()) {
+        *x = x.conj() * y;
+    }
+    ifft.process(&mut fa);
+
+    //rustfft does not normalize the inverse transform
+    let scale = 1.0 / size as f32;
+    fa.iter().take(lags).map(|c| c.re * scale).collect()
+}
+
+///Refines the extremum at index i with the parabola going through its neighbours,
+///returns the sub-sample position and the value at that position
+pub(crate) fn parabolic_interpolation(values: &[f32], i: usize) -> (f32, f32) {
+    if i == 0 || i + 1 >= values.len() {
+        return (i as f32, values[i]);
+    }
+    let (left, center, right) = (values[i - 1], values[i], values[i + 1]);
+    let denominator = left - 2.0 * center + right;
+    if denominator.abs() < f32::EPSILON {
+        return (i as f32, center);
+    }
+    let shift = 0.5 * (left - right) / denominator;
+    (i as f32 + shift, center - 0.25 * (left - right) * shift)
+}
+
+//Synthetic signals the detectors are tested on, at the default window of the DSP
+#[cfg(test)]
+pub(crate) mod signals {
+    use super::PitchDetector;
+    use std::f32::consts::TAU;
+
+    pub const SAMPLE_RATE: f32 = 48000.0;
+    const SIZE: usize = 4096;
+
+    //the lowest B of a 5 string bass and the highest C of a piano, the ends of the range
+    pub const LOWEST: f32 = 30.87;
+    pub const HIGHEST: f32 = 4186.0;
+
+    pub fn sine(frequency: f32) -> Vec<f32> {
+        (0..SIZE)
+            .map(|i| 0.5 * (TAU * frequency * i as f32 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    //every harmonic under Nyquist at 1/k, so it does not alias near the top of the range
+    pub fn saw(frequency: f32) -> Vec<f32> {
+        let harmonics = (SAMPLE_RATE / 2.0 / frequency) as usize;
+        (0..SIZE)
+            .map(|i| {
+                let phase = TAU * frequency * i as f32 / SAMPLE_RATE;
+                0.3 * (1..=harmonics)
+                    .map(|k| (k as f32 * phase).sin() / k as f32)
+                    .sum::<f32>()
+            })
+            .collect()
+    }
+
+    //white noise from a xorshift, the same at each run
+    pub fn noise() -> Vec<f32> {
+        let mut state = 0x2545_f491_u32;
+        (0..SIZE)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                0.5 * (state as f32 / u32::MAX as f32 * 2.0 - 1.0)
+            })
+            .collect()
+    }
+
+    pub fn silence() -> Vec<f32> {
+        vec![0.0; SIZE]
+    }
+
+    //an octave error is 1200 cents off, far out of any tolerance
+    pub fn assert_pitch(
+        detector: &mut dyn PitchDetector,
+        signal: &[f32],
+        frequency: f32,
+        tolerance_cents: f32,
+    ) {
+        let estimate = detector
+            .detect(signal, SAMPLE_RATE)
+            .unwrap_or_else(|| panic!("no pitch found at {} Hz", frequency));
+        let cents = 1200.0 * (estimate.frequency / frequency).log2();
+        assert!(
+            cents.abs() <= tolerance_cents,
+            "{} Hz found at {} Hz, {:+.1} cents",
+            frequency,
+            estimate.frequency,
+            cents
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use super::{
+    MAX_FREQUENCY, MIN_FREQUENCY, PitchDetector, PitchEstimate, cross_correlation,
+    parabolic_interpolation,
+};
+use rustfft::FftPlanner;
+
+///YIN (de Cheveigné & Kawahara, 2002) : the cumulative mean normalized difference function
+///avoids the octave errors of a plain autocorrelation, the first dip under threshold is the period
+pub struct Yin {
+    pub threshold: f32,
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    planner: FftPlanner<f32>,
+}
+
+impl Default for Yin {
+    fn default() -> Self {
+        Self {
+            threshold: 0.15,
+            min_frequency: MIN_FREQUENCY,
+            max_frequency: MAX_FREQUENCY,
+            planner: FftPlanner::new(),
+        }
+    }
+}
+
+impl PitchDetector for Yin {
+    fn detect(&mut self, buffer: &[f32], sample_rate: f32) -> Option<PitchEstimate> {
+        //we compare a window of half the buffer with itself shifted by up to half the buffer
+        let window = buffer.len() / 2;
+        let min_tau = ((sample_rate / self.max_frequency) as usize).max(2);
+        let max_tau = ((sample_rate / self.min_frequency) as usize).min(window);
+        if max_tau <= min_tau + 1 {
+            return None;
+        }
+
+        //d(tau) = sum (x[j] - x[j + tau])² = energy(0) + energy(tau) - 2 r(tau)
+        let r = cross_correlation(&mut self.planner, &buffer[..window], buffer, max_tau + 1);
+        let mut prefix = Vec::with_capacity(buffer.len() + 1);
+        prefix.push(0.0f64);
+        for &x in buffer {
+            prefix.push(prefix[prefix.len() - 1] + (x as f64) * (x as f64));
+        }
+        let energy = |start: usize| (prefix[start + window] - prefix[start]) as f32;
+
+        //cumulative mean normalized difference
+        let mut cmnd = vec![1.0f32; max_tau + 1];
+        let mut running_sum = 0.0f32;
+        for tau in 1..=max_tau {
+            let difference = (energy(0) + energy(tau) - 2.0 * r[tau]).max(0.0);
+            running_sum += difference;
+            cmnd[tau] = if running_sum > 0.0 {
+                difference * tau as f32 / running_sum
+            } else {
+                1.0
+            };
+        }
+
+        //absolute threshold, then we slide down to the bottom of that dip
+        let mut tau = (min_tau..max_tau).find(|&tau| cmnd[tau] < self.threshold)?;
+        while tau + 1 < max_tau && cmnd[tau + 1] < cmnd[tau] {
+            tau += 1;
+        }
+
+        let (period, value) = parabolic_interpolation(&cmnd, tau);
+        if period <= 0.0 {
+            return None;
+        }
+        Some(PitchEstimate {
+            frequency: sample_rate / period,
+            clarity: (1.0 - value).clamp(0.0, 1.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::signals::*;
+
+    #[test]
+    fn sines_across_the_range() {
+        for frequency in [LOWEST, 41.2, 440.0, 2093.0, HIGHEST] {
+            assert_pitch(&mut Yin::default(), &sine(frequency), frequency, 3.0);
+        }
+    }
+
+    //the harmonics of a saw must not be taken for the fundamental, nor its octave below
+    #[test]
+    fn saws_across_the_range() {
+        for frequency in [LOWEST, 41.2, 440.0, 2093.0, HIGHEST] {
+            assert_pitch(&mut Yin::default(), &saw(frequency), frequency, 7.0);
+        }
+    }
+
+    #[test]
+    fn no_pitch_in_silence_or_noise() {
+        let mut detector = Yin::default();
+        assert_eq!(detector.detect(&silence(), SAMPLE_RATE), None);
+        assert_eq!(detector.detect(&noise(), SAMPLE_RATE), None);
+    }
+}
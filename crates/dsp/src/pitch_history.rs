@@ -0,0 +1,89 @@
+use crate::{Analysis, Note};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+
+///How the pitch history plots the pitch vertically
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PitchAxis {
+    ///Cents from the nearest note, the intonation whatever the note
+    Cents,
+    ///Notes on a semitone scale, the melody
+    Notes,
+}
+
+impl fmt::Display for PitchAxis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PitchAxis::Cents => "Cents",
+            PitchAxis::Notes => "Notes",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PitchAxis {
+    pub const ALL: [PitchAxis; 2] = [PitchAxis::Cents, PitchAxis::Notes];
+}
+
+///One analysis : None when nothing was voiced, which leaves a gap in the graph
+#[derive(Debug, Clone, Copy)]
+pub struct PitchPoint {
+    pub time: f64,
+    pub note: Option<Note>,
+}
+
+impl PitchPoint {
+    ///Height on the Notes axis, in semitones : the MIDI note plus its cents
+    pub fn semitones(&self) -> Option<f32> {
+        self.note.map(|n| n.midi as f32 + n.cents / 100.0)
+    }
+}
+
+///Pitch of the analyses of the last seconds, oldest first. Points follow the stream time of the
+///analyses, not the frames, so the graph scrolls at the same speed whatever the frame rate
+pub struct PitchHistory {
+    points: VecDeque<PitchPoint>,
+    pub seconds: f32,
+}
+
+impl PitchHistory {
+    pub fn new(seconds: f32) -> Self {
+        Self {
+            points: VecDeque::new(),
+            seconds,
+        }
+    }
+
+    ///Adds the analysis if it is a new one, and forgets what scrolled out
+    pub fn push(&mut self, analysis: &Analysis) {
+        if self
+            .points
+            .back()
+            .is_some_and(|last| last.time >= analysis.time)
+        {
+            return;
+        }
+        self.points.push_back(PitchPoint {
+            time: analysis.time,
+            note: analysis.note,
+        });
+        let oldest = analysis.time - self.seconds as f64;
+        while self.points.front().is_some_and(|p| p.time < oldest) {
+            self.points.pop_front();
+        }
+    }
+
+    pub fn points(&self) -> &VecDeque<PitchPoint> {
+        &self.points
+    }
+
+    ///Time of the latest analysis, the right edge of the graph
+    pub fn latest(&self) -> f64 {
+        self.points.back().map_or(0.0, |p| p.time)
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+///How frequencies are spread along an axis
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FrequencyAxis {
+    Linear,
+    Log,
+}
+
+impl fmt::Display for FrequencyAxis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrequencyAxis::Linear => write!(f, "Linear"),
+            FrequencyAxis::Log => write!(f, "Log"),
+        }
+    }
+}
+
+impl FrequencyAxis {
+    ///Frequency found at t, from 0.0 (min) to 1.0 (max), along the axis
+    pub fn frequency(self, t: f32, min: f32, max: f32) -> f32 {
+        match self {
+            FrequencyAxis::Linear => min + (max - min) * t,
+            FrequencyAxis::Log => min * (max / min).powf(t),
+        }
+    }
+}
+
+///Time history of spectrum frames, oldest first, for the scrolling spectrogram.
+///Frames are immutable once pushed, and the history itself is shared with the snapshots : a
+///snapshot costs a reference count, and a push only copies the list of frames while a reader
+///still holds the previous one
+pub struct Spectrogram {
+    frames: Arc<VecDeque<Arc<[f32]>>>,
+    capacity: usize,
+}
+
+impl Spectrogram {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: Arc::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    ///Adds the latest magnitudes in dB, dropping the oldest frame when full.
+    ///A frame of another size means the FFT size changed : the old frames can not be drawn with
+    ///the new ones, so we start over
+    pub fn push(&mut self, magnitudes_db: &[f32]) {
+        let frames = Arc::make_mut(&mut self.frames);
+        if frames
+            .back()
+            .is_some_and(|last| last.len() != magnitudes_db.len())
+        {
+            frames.clear();
+        }
+        //the oldest frame is written over when no snapshot holds it anymore
+        let mut recycled = None;
+        while frames.len() >= self.capacity {
+            recycled = frames.pop_front();
+        }
+        let frame = match recycled {
+            Some(mut frame) if frame.len() == magnitudes_db.len() => {
+                match Arc::get_mut(&mut frame) {
+                    Some(free) => {
+                        free.copy_from_slice(magnitudes_db);
+                        frame
+                    }
+                    None => Arc::from(magnitudes_db),
+                }
+            }
+            _ => Arc::from(magnitudes_db),
+        };
+        frames.push_back(frame);
+    }
+
+    pub fn frames(&self) -> &Arc<VecDeque<Arc<[f32]>>> {
+        &self.frames
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        if self.frames.len() > self.capacity {
+            let frames = Arc::make_mut(&mut self.frames);
+            while frames.len() > self.capacity {
+                frames.pop_front();
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames = Arc::default();
+    }
+}
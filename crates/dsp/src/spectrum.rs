@@ -0,0 +1,282 @@
+use clap::ValueEnum;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+use std::fmt;
+use std::sync::Arc;
+
+///The FFT sizes we offer : powers of two, from 512 (fast, coarse) to 32768 (slow, fine)
+pub const FFT_SIZES: [usize; 7] = [512, 1024, 2048, 4096, 8192, 16384, 32768];
+
+//Anything quieter is drawn at the floor, it also avoids log10(0)
+pub const MIN_DB: f32 = -160.0;
+
+///Window applied to the samples before the FFT, trading frequency resolution against leakage
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Serialize, Deserialize)]
+pub enum WindowFunction {
+    Hann,
+    Hamming,
+    BlackmanHarris,
+    FlatTop,
+}
+
+impl fmt::Display for WindowFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WindowFunction::Hann => "Hann",
+            WindowFunction::Hamming => "Hamming",
+            WindowFunction::BlackmanHarris => "Blackman-Harris",
+            WindowFunction::FlatTop => "Flat-top",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl WindowFunction {
+    pub const ALL: [WindowFunction; 4] = [
+        WindowFunction::Hann,
+        WindowFunction::Hamming,
+        WindowFunction::BlackmanHarris,
+        WindowFunction::FlatTop,
+    ];
+
+    //cosine sum coefficients a0 - a1 cos(x) + a2 cos(2x) - ...
+    fn cosine_terms(self) -> &'static [f64] {
+        match self {
+            WindowFunction::Hann => &[0.5, 0.5],
+            WindowFunction::Hamming => &[0.54, 0.46],
+            WindowFunction::BlackmanHarris => &[0.35875, 0.48829, 0.14128, 0.01168],
+            WindowFunction::FlatTop => &[
+                0.215_578_95,
+                0.416_631_58,
+                0.277_263_158,
+                0.083_578_947,
+                0.006_947_368,
+            ],
+        }
+    }
+
+    ///Periodic window of size samples, the form meant for spectral analysis
+    pub fn coefficients(self, size: usize) -> Vec<f32> {
+        let terms = self.cosine_terms();
+        (0..size)
+            .map(|n| {
+                let x = TAU * n as f64 / size as f64;
+                terms
+                    .iter()
+                    .enumerate()
+                    .map(|(k, a)| {
+                        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+                        sign * a * (k as f64 * x).cos()
+                    })
+                    .sum::<f64>() as f32
+            })
+            .collect()
+    }
+}
+
+///Windowed FFT over the latest size samples.
+///It keeps its own history, so a big FFT still works when each update brings fewer samples.
+pub struct Spectrum {
+    size: usize,
+    window: WindowFunction,
+    coefficients: Vec<f32>,
+    //sum of the window, to scale a full scale sine to 0 dB whatever the window
+    coherent_gain: f32,
+    planner: FftPlanner<f32>,
+    fft: Arc<dyn Fft<f32>>,
+    history: Vec<f32>,
+    write_pos: usize,
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    magnitudes_db: Vec<f32>,
+}
+
+impl Spectrum {
+    pub fn new(size: usize, window: WindowFunction) -> Self {
+        let mut planner = FftPlanner::new();
+        let fft = planner.plan_fft_forward(size);
+        let mut spectrum = Self {
+            size,
+            window,
+            coefficients: Vec::new(),
+            coherent_gain: 1.0,
+            planner,
+            fft,
+            history: vec![0.0; size],
+            write_pos: 0,
+            buffer: Vec::new(),
+            scratch: Vec::new(),
+            magnitudes_db: vec![MIN_DB; size / 2 + 1],
+        };
+        spectrum.set_size(size);
+        spectrum
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn window(&self) -> WindowFunction {
+        self.window
+    }
+
+    ///Changes the FFT size : the history restarts from silence
+    pub fn set_size(&mut self, size: usize) {
+        let size = size.clamp(FFT_SIZES[0], FFT_SIZES[FFT_SIZES.len() - 1]);
+        self.size = size;
+        self.fft = self.planner.plan_fft_forward(size);
+        self.history = vec![0.0; size];
+        self.write_pos = 0;
+        self.buffer = vec![Complex::default(); size];
+        self.scratch = vec![Complex::default(); self.fft.get_inplace_scratch_len()];
+        self.magnitudes_db = vec![MIN_DB; size / 2 + 1];
+        self.set_window(self.window);
+    }
+
+    pub fn set_window(&mut self, window: WindowFunction) {
+        self.window = window;
+        self.coefficients = window.coefficients(self.size);
+        self.coherent_gain = self.coefficients.iter().sum::<f32>().max(f32::EPSILON);
+    }
+
+    ///Appends new samples to the history, only the last size samples are kept
+    pub fn push(&mut self, samples: &[f32]) {
+        let samples = &samples[samples.len().saturating_sub(self.size)..];
+        for &sample in samples {
+            self.history[self.write_pos] = sample;
+            self.write_pos = (self.write_pos + 1) % self.size;
+        }
+    }
+
+    ///Runs the FFT on the history, oldest sample first, and updates the magnitudes
+    pub fn compute(&mut self) {
+        let (newest, oldest) = self.history.split_at(self.write_pos);
+        for ((slot, &sample), &w) in self
+            .buffer
+            .iter_mut()
+            .zip(oldest.iter().chain(newest.iter()))
+            .zip(self.coefficients.iter())
+        {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        self.fft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+
+        //one sided spectrum : the energy of negative frequencies is folded, hence the 2. DC and
+        //Nyquist have no negative twin, they are not doubled
+        let nyquist = self.size / 2;
+        for (i, (db, bin)) in self
+            .magnitudes_db
+            .iter_mut()
+            .zip(self.buffer.iter())
+            .enumerate()
+        {
+            let folded = if i == 0 || i == nyquist { 1.0 } else { 2.0 };
+            let amplitude = bin.norm() * folded / self.coherent_gain;
+            *db = (20.0 * amplitude.log10()).max(MIN_DB);
+        }
+    }
+
+    ///Magnitude in dBFS of each bin, from 0 Hz to Nyquist
+    pub fn magnitudes_db(&self) -> &[f32] {
+        &self.magnitudes_db
+    }
+
+    pub fn bin_frequency(&self, bin: usize, sample_rate: f32) -> f32 {
+        bin as f32 * sample_rate / self.size as f32
+    }
+
+    ///Loudest bin, ignoring DC, as (frequency, dB)
+    pub fn peak(&self, sample_rate: f32) -> Option<(f32, f32)> {
+        self.magnitudes_db
+            .iter()
+            .enumerate()
+            .skip(1)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .filter(|(_, db)| **db > MIN_DB)
+            .map(|(bin, &db)| (self.bin_frequency(bin, sample_rate), db))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn spectrum_of(samples: &[f32], window: WindowFunction) -> Spectrum {
+        let mut spectrum = Spectrum::new(samples.len(), window);
+        spectrum.push(samples);
+        spectrum.compute();
+        spectrum
+    }
+
+    #[test]
+    fn window_coefficients() {
+        let size = 1024;
+        for window in WindowFunction::ALL {
+            let w = window.coefficients(size);
+            //periodic : symmetric around the middle, where the window peaks at 1
+            for n in 1..size / 2 {
+                assert!(
+                    (w[n] - w[size - n]).abs() < 1e-6,
+                    "{} is not symmetric",
+                    window
+                );
+            }
+            assert!(
+                (w[size / 2] - 1.0).abs() < 1e-3,
+                "{} peaks at {}",
+                window,
+                w[size / 2]
+            );
+        }
+        let hann = WindowFunction::Hann.coefficients(size);
+        assert!(hann[0].abs() < 1e-6);
+        let hamming = WindowFunction::Hamming.coefficients(size);
+        assert!((hamming[0] - 0.08).abs() < 1e-6);
+        //the coherent gain of a Hann window is one half
+        let gain = hann.iter().sum::<f32>() / size as f32;
+        assert!((gain - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn bin_frequencies() {
+        let spectrum = Spectrum::new(4096, WindowFunction::Hann);
+        assert_eq!(spectrum.magnitudes_db().len(), 2049);
+        assert_eq!(spectrum.bin_frequency(0, SAMPLE_RATE), 0.0);
+        assert_eq!(spectrum.bin_frequency(1, SAMPLE_RATE), SAMPLE_RATE / 4096.0);
+        assert_eq!(spectrum.bin_frequency(2048, SAMPLE_RATE), SAMPLE_RATE / 2.0);
+    }
+
+    //whatever the window, a full scale sine centred on a bin reads 0 dBFS at that bin
+    #[test]
+    fn full_scale_sine_reads_0_dbfs() {
+        let (size, bin) = (4096, 64);
+        let frequency = bin as f32 * SAMPLE_RATE / size as f32;
+        let samples: Vec<f32> = (0..size)
+            .map(|i| (std::f32::consts::TAU * frequency * i as f32 / SAMPLE_RATE).sin())
+            .collect();
+        for window in WindowFunction::ALL {
+            let spectrum = spectrum_of(&samples, window);
+            let (peak, db) = spectrum.peak(SAMPLE_RATE).unwrap();
+            assert_eq!(peak, frequency);
+            assert!(db.abs() < 0.01, "{} reads {} dBFS", window, db);
+        }
+    }
+
+    //DC and Nyquist have no negative frequency folded on them
+    #[test]
+    fn dc_and_nyquist_are_not_doubled() {
+        let size = 4096;
+        let dc = spectrum_of(&vec![1.0; size], WindowFunction::Hann);
+        assert!(dc.magnitudes_db()[0].abs() < 0.01);
+        let alternating: Vec<f32> = (0..size)
+            .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        let nyquist = spectrum_of(&alternating, WindowFunction::Hann);
+        assert!(nyquist.magnitudes_db()[size / 2].abs() < 0.01);
+    }
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+
+///What the oscilloscope waits for before drawing the trace
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TriggerMode {
+    ///No trigger : the trace shows the latest samples and runs free
+    Off,
+    Rising,
+    Falling,
+    ///Locks on the phase of the detected fundamental, so harmonics crossing the level several
+    ///times per period do not make the trace jump
+    Pitch,
+}
+
+impl fmt::Display for TriggerMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TriggerMode::Off => "Off",
+            TriggerMode::Rising => "Rising",
+            TriggerMode::Falling => "Falling",
+            TriggerMode::Pitch => "Pitch",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl TriggerMode {
+    pub const ALL: [TriggerMode; 4] = [
+        TriggerMode::Off,
+        TriggerMode::Rising,
+        TriggerMode::Falling,
+        TriggerMode::Pitch,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Trigger {
+    pub mode: TriggerMode,
+    ///Level the edge has to cross, in full scale
+    pub level: f32,
+    ///How far back under the level the signal has to go before the next edge counts, so the
+    ///noise around the level does not trigger on every sample
+    pub hysteresis: f32,
+}
+
+impl Default for Trigger {
+    fn default() -> Self {
+        Self {
+            mode: TriggerMode::Rising,
+            level: 0.0,
+            hysteresis: 0.02,
+        }
+    }
+}
+
+impl Trigger {
+    ///Where the trace starts in samples, between two samples : the latest trigger still followed
+    ///by span samples. period is the one of the detected pitch, in samples, for the Pitch mode.
+    ///None when nothing triggered, the caller decides whether to run free
+    pub fn find(&self, samples: &[f32], span: usize, period: Option<f32>) -> Option<f32> {
+        let last = samples.len().checked_sub(span.max(1))?;
+        match self.mode {
+            TriggerMode::Off => None,
+            TriggerMode::Rising => edge(samples, last, self.level, self.hysteresis, 1.0),
+            //a falling edge is a rising one of the inverted signal
+            TriggerMode::Falling => edge(samples, last, -self.level, self.hysteresis, -1.0),
+            TriggerMode::Pitch => fundamental_phase(samples, last, period?),
+        }
+    }
+}
+
+fn edge(samples: &[f32], last: usize, level: f32, hysteresis: f32, sign: f32) -> Option<f32> {
+    let mut armed = false;
+    let mut found = None;
+    for (i, pair) in samples.windows(2).take(last).enumerate() {
+        let (a, b) = (sign * pair[0], sign * pair[1]);
+        if a < level - hysteresis {
+            armed = true;
+        }
+        if armed && a < level && b >= level {
+            found = Some(i as f32 + (level - a) / (b - a));
+            armed = false;
+        }
+    }
+    found
+}
+
+//The phase of the fundamental is measured with one DFT bin over whole periods, and the trace
+//starts where the fundamental alone would cross zero going up
+fn fundamental_phase(samples: &[f32], last: usize, period: f32) -> Option<f32> {
+    if period.is_nan() || period < 2.0 {
+        return None;
+    }
+    let period = period as f64;
+    let length = ((samples.len() as f64 / period).floor() * period) as usize;
+    if length == 0 {
+        return None;
+    }
+    let start = samples.len() - length;
+    let omega = 2.0 * PI / period;
+    let (mut re, mut im) = (0.0, 0.0);
+    for (n, &s) in samples.iter().enumerate().skip(start) {
+        let angle = omega * n as f64;
+        re += s as f64 * angle.cos();
+        im -= s as f64 * angle.sin();
+    }
+    if re == 0.0 && im == 0.0 {
+        return None;
+    }
+    //the fundamental is cos(omega * n + phase), it rises through zero at -pi / 2
+    let phase = im.atan2(re);
+    let first = (-PI / 2.0 - phase) / omega;
+    let n = first + ((last as f64 - first) / period).floor() * period;
+    (n >= 0.0).then_some(n as f32)
+}
+
+///The signal at a position between two samples, linearly interpolated
+pub fn interpolate(samples: &[f32], position: f32) -> f32 {
+    if samples.is_empty() || position < 0.0 {
+        return 0.0;
+    }
+    let i = position.floor() as usize;
+    let t = position - i as f32;
+    match (samples.get(i), samples.get(i + 1)) {
+        (Some(a), Some(b)) => a + (b - a) * t,
+        (Some(a), None) => *a,
+        _ => 0.0,
+    }
+}
@@ -0,0 +1,248 @@
+use crate::note::{self, NOTE_NAMES};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub mod scala;
+
+pub use scala::{KeyboardMapping, Scale};
+
+///Number of MIDI keys, we compute a target frequency for each of them
+pub const MIDI_KEYS: usize = 128;
+
+///Historical temperaments of the 12 notes octave
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Serialize, Deserialize)]
+pub enum Temperament {
+    Equal,
+    ///5-limit just intonation built on the key
+    Just,
+    Pythagorean,
+    ///Quarter comma meantone
+    Meantone,
+    Werckmeister3,
+    Vallotti,
+}
+
+impl fmt::Display for Temperament {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Temperament::Equal => "Equal",
+            Temperament::Just => "Just intonation",
+            Temperament::Pythagorean => "Pythagorean",
+            Temperament::Meantone => "1/4 comma meantone",
+            Temperament::Werckmeister3 => "Werckmeister III",
+            Temperament::Vallotti => "Vallotti",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn ratio_cents(numerator: f64, denominator: f64) -> f64 {
+    1200.0 * (numerator / denominator).log2()
+}
+
+impl Temperament {
+    pub const ALL: [Temperament; 6] = [
+        Temperament::Equal,
+        Temperament::Just,
+        Temperament::Pythagorean,
+        Temperament::Meantone,
+        Temperament::Werckmeister3,
+        Temperament::Vallotti,
+    ];
+
+    ///Cents of each of the 12 degrees above the key
+    pub fn cents(self) -> [f64; 12] {
+        match self {
+            Temperament::Equal => std::array::from_fn(|i| 100.0 * i as f64),
+            Temperament::Just => [
+                (1.0, 1.0),
+                (16.0, 15.0),
+                (9.0, 8.0),
+                (6.0, 5.0),
+                (5.0, 4.0),
+                (4.0, 3.0),
+                (45.0, 32.0),
+                (3.0, 2.0),
+                (8.0, 5.0),
+                (5.0, 3.0),
+                (9.0, 5.0),
+                (15.0, 8.0),
+            ]
+            .map(|(n, d)| ratio_cents(n, d)),
+            Temperament::Pythagorean => [
+                (1.0, 1.0),
+                (256.0, 243.0),
+                (9.0, 8.0),
+                (32.0, 27.0),
+                (81.0, 64.0),
+                (4.0, 3.0),
+                (729.0, 512.0),
+                (3.0, 2.0),
+                (128.0, 81.0),
+                (27.0, 16.0),
+                (16.0, 9.0),
+                (243.0, 128.0),
+            ]
+            .map(|(n, d)| ratio_cents(n, d)),
+            Temperament::Meantone => {
+                //a chain of fifths narrowed so four of them make a pure major third,
+                //from Eb (3 fifths down) to G# (8 fifths up)
+                let fifth = ratio_cents(5.0, 1.0) / 4.0;
+                let mut cents = [0.0; 12];
+                for k in -3i32..=8 {
+                    let degree = (k * 7).rem_euclid(12) as usize;
+                    cents[degree] = (k as f64 * fifth).rem_euclid(1200.0);
+                }
+                cents
+            }
+            Temperament::Werckmeister3 => [
+                0.0, 90.225, 192.18, 294.135, 390.225, 498.045, 588.27, 696.09, 792.18, 888.27,
+                996.09, 1092.18,
+            ],
+            Temperament::Vallotti => [
+                0.0, 94.135, 196.09, 298.045, 392.18, 501.955, 592.18, 698.045, 796.09, 894.135,
+                1000.0, 1090.225,
+            ],
+        }
+    }
+}
+
+///Parses a pitch class like C, F#, Bb or eb, and returns its index from C
+pub fn parse_key(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let mut chars = s.chars();
+    let letter = chars.next().map(|c| c.to_ascii_uppercase());
+    let base = match letter {
+        Some('C') => 0,
+        Some('D') => 2,
+        Some('E') => 4,
+        Some('F') => 5,
+        Some('G') => 7,
+        Some('A') => 9,
+        Some('B') => 11,
+        _ => {
+            return Err(format!(
+                "Invalid key \"{}\": expected a note like C, F# or Bb",
+                s
+            ));
+        }
+    };
+    let accidental = match chars.as_str() {
+        "" => 0,
+        "#" | "♯" => 1,
+        "b" | "♭" => -1,
+        _ => {
+            return Err(format!(
+                "Invalid key \"{}\": expected a note like C, F# or Bb",
+                s
+            ));
+        }
+    };
+    Ok((base + accidental as i32).rem_euclid(12) as usize)
+}
+
+///How notes are tuned : a temperament transposed to a key, or a Scala scale and its mapping
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TuningSystem {
+    Temperament {
+        temperament: Temperament,
+        ///Pitch class the temperament is built on, 0 is C
+        key: usize,
+    },
+    Scala {
+        scale: Scale,
+        mapping: Option<KeyboardMapping>,
+    },
+}
+
+impl Default for TuningSystem {
+    fn default() -> Self {
+        TuningSystem::Temperament {
+            temperament: Temperament::Equal,
+            key: 0,
+        }
+    }
+}
+
+impl fmt::Display for TuningSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TuningSystem::Temperament {
+                temperament: Temperament::Equal,
+                ..
+            } => write!(f, "{}", Temperament::Equal),
+            TuningSystem::Temperament { temperament, key } => {
+                write!(f, "{} in {}", temperament, NOTE_NAMES[key % 12])
+            }
+            TuningSystem::Scala { scale, .. } => write!(f, "Scala: {}", scale.description),
+        }
+    }
+}
+
+impl TuningSystem {
+    ///Degree of the scale played by a MIDI key, when the scale is not named like a keyboard :
+    ///only Scala scales of another size than 12 are
+    pub fn degree(&self, midi: i32) -> Option<usize> {
+        let TuningSystem::Scala { scale, mapping } = self else {
+            return None;
+        };
+        if scale.len() == 12 {
+            return None;
+        }
+        let degree = match mapping {
+            Some(mapping) => mapping.degree(midi as i64, scale)?,
+            None => KeyboardMapping::linear(note::DEFAULT_A4 as f64).degree(midi as i64, scale)?,
+        };
+        Some(degree.rem_euclid(scale.len() as i64) as usize)
+    }
+
+    ///Target frequency of every MIDI key, None for keys a keyboard mapping leaves unmapped.
+    ///Temperaments keep A4 on a4, a Scala mapping has its own reference frequency
+    pub fn frequencies(&self, a4: f32) -> Vec<Option<f32>> {
+        match self {
+            TuningSystem::Temperament { temperament, key } => {
+                let cents = temperament.cents();
+                //deviation from equal temperament of a pitch class
+                let deviation = |pitch_class: usize| {
+                    let degree = (pitch_class + 12 - key % 12) % 12;
+                    cents[degree] - 100.0 * degree as f64
+                };
+                let a_deviation = deviation(9);
+                (0..MIDI_KEYS)
+                    .map(|midi| {
+                        let cents =
+                            100.0 * (midi as f64 - 69.0) + deviation(midi % 12) - a_deviation;
+                        Some((a4 as f64 * 2f64.powf(cents / 1200.0)) as f32)
+                    })
+                    .collect()
+            }
+            TuningSystem::Scala { scale, mapping } => {
+                let mapping = mapping
+                    .clone()
+                    .unwrap_or_else(|| KeyboardMapping::linear(a4 as f64));
+                mapping.frequencies(scale)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_by_degree_out_of_12_notes() {
+        let pentatonic =
+            Scale::parse("! 5 notes\nSlendro\n5\n!\n240.0\n480.0\n720.0\n960.0\n2/1\n").unwrap();
+        let tuning = TuningSystem::Scala {
+            scale: pentatonic,
+            mapping: None,
+        };
+        //the tonic is on middle C, each key plays the next degree
+        assert_eq!(tuning.degree(60), Some(0));
+        assert_eq!(tuning.degree(62), Some(2));
+        assert_eq!(tuning.degree(66), Some(1));
+        assert_eq!(TuningSystem::default().degree(60), None);
+    }
+}
@@ -0,0 +1,227 @@
+use super::MIDI_KEYS;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+//Scala files : https://www.huygens-fokker.org/scala/scl_format.html
+//Lines starting with ! are comments, only the first token of a line matters
+
+fn data_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim_start().starts_with('!'))
+}
+
+fn first_token(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or("")
+}
+
+///A scale from a .scl file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scale {
+    pub description: String,
+    ///Cents of each degree above the tonic, the last one is the period (usually the octave)
+    pub degrees: Vec<f64>,
+}
+
+//A pitch is in cents when it has a dot, otherwise it is a ratio like 3/2 or a whole number
+fn parse_pitch(token: &str) -> Result<f64, String> {
+    if token.contains('.') {
+        return token
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid cents value \"{}\"", token));
+    }
+    let (numerator, denominator) = token.split_once('/').unwrap_or((token, "1"));
+    let numerator: f64 = numerator
+        .parse()
+        .map_err(|_| format!("Invalid ratio \"{}\"", token))?;
+    let denominator: f64 = denominator
+        .parse()
+        .map_err(|_| format!("Invalid ratio \"{}\"", token))?;
+    if numerator <= 0.0 || denominator <= 0.0 {
+        return Err(format!("Ratio \"{}\" must be positive", token));
+    }
+    Ok(1200.0 * (numerator / denominator).log2())
+}
+
+impl Scale {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = data_lines(text);
+        let description = lines.next().ok_or("Empty scale file")?.trim().to_string();
+        let count_line = lines.next().ok_or("Missing the number of notes")?;
+        let count: usize = first_token(count_line)
+            .parse()
+            .map_err(|_| format!("Invalid number of notes \"{}\"", count_line.trim()))?;
+        let degrees = lines
+            .map(first_token)
+            .filter(|token| !token.is_empty())
+            .take(count)
+            .map(parse_pitch)
+            .collect::<Result<Vec<_>, _>>()?;
+        if degrees.len() != count {
+            return Err(format!(
+                "Expected {} notes but found {}",
+                count,
+                degrees.len()
+            ));
+        }
+        if count == 0 {
+            return Err("A scale needs at least one note".to_string());
+        }
+        Ok(Self {
+            description,
+            degrees,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text)
+    }
+
+    ///Notes per period
+    pub fn len(&self) -> usize {
+        self.degrees.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.degrees.is_empty()
+    }
+
+    ///Cents of any degree above the tonic, going through as many periods as needed
+    pub fn cents(&self, degree: i64) -> f64 {
+        let size = self.len() as i64;
+        let period = self.degrees[self.len() - 1];
+        let index = degree.rem_euclid(size) as usize;
+        let base = if index == 0 {
+            0.0
+        } else {
+            self.degrees[index - 1]
+        };
+        base + degree.div_euclid(size) as f64 * period
+    }
+}
+
+///A keyboard mapping from a .kbm file : which scale degree each MIDI key plays
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyboardMapping {
+    ///Keys in one repetition of the mapping, 0 maps every key to the next degree
+    pub size: usize,
+    pub first_key: i64,
+    pub last_key: i64,
+    ///Key playing the tonic of the scale
+    pub middle_key: i64,
+    pub reference_key: i64,
+    pub reference_frequency: f64,
+    ///Degree of the scale a repetition of the mapping spans, 0 means the whole scale
+    pub octave_degree: i64,
+    ///Degree played by each key of the repetition, None when the key is silent
+    pub keys: Vec<Option<i64>>,
+}
+
+impl KeyboardMapping {
+    ///What Scala does without a .kbm : the tonic on middle C, A4 as reference
+    pub fn linear(a4: f64) -> Self {
+        Self {
+            size: 0,
+            first_key: 0,
+            last_key: MIDI_KEYS as i64 - 1,
+            middle_key: 60,
+            reference_key: 69,
+            reference_frequency: a4,
+            octave_degree: 0,
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut tokens = data_lines(text)
+            .map(first_token)
+            .filter(|token| !token.is_empty());
+        let mut header = |name: &str| -> Result<&str, String> {
+            tokens
+                .next()
+                .ok_or(format!("Missing {} in keyboard mapping", name))
+        };
+        let integer = |token: &str, name: &str| -> Result<i64, String> {
+            token
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid {} \"{}\"", name, token))
+        };
+        let size = integer(header("map size")?, "map size")?;
+        let first_key = integer(header("first key")?, "first key")?;
+        let last_key = integer(header("last key")?, "last key")?;
+        let middle_key = integer(header("middle key")?, "middle key")?;
+        let reference_key = integer(header("reference key")?, "reference key")?;
+        let frequency_token = header("reference frequency")?;
+        let reference_frequency: f64 = frequency_token
+            .parse()
+            .map_err(|_| format!("Invalid reference frequency \"{}\"", frequency_token))?;
+        let octave_degree = integer(header("octave degree")?, "octave degree")?;
+        if size < 0 || reference_frequency <= 0.0 {
+            return Err("Invalid keyboard mapping header".to_string());
+        }
+        //missing entries at the end are silent keys, like x
+        let mut keys = Vec::with_capacity(size as usize);
+        for token in tokens.take(size as usize) {
+            if token.eq_ignore_ascii_case("x") {
+                keys.push(None);
+            } else {
+                keys.push(Some(integer(token, "degree")?));
+            }
+        }
+        keys.resize(size as usize, None);
+        Ok(Self {
+            size: size as usize,
+            first_key,
+            last_key,
+            middle_key,
+            reference_key,
+            reference_frequency,
+            octave_degree,
+            keys,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text)
+    }
+
+    ///Scale degree played by key, from the tonic on middle_key
+    pub fn degree(&self, key: i64, scale: &Scale) -> Option<i64> {
+        if key < self.first_key || key > self.last_key {
+            return None;
+        }
+        let offset = key - self.middle_key;
+        if self.size == 0 {
+            return Some(offset);
+        }
+        let size = self.size as i64;
+        let octave_degree = if self.octave_degree > 0 {
+            self.octave_degree
+        } else {
+            scale.len() as i64
+        };
+        self.keys[offset.rem_euclid(size) as usize]
+            .map(|degree| degree + offset.div_euclid(size) * octave_degree)
+    }
+
+    ///Frequency of every MIDI key, the reference key sounding at the reference frequency
+    pub fn frequencies(&self, scale: &Scale) -> Vec<Option<f32>> {
+        //the reference key might be silent, its degree still anchors the tuning
+        let reference_degree = self
+            .degree(self.reference_key, scale)
+            .unwrap_or(self.reference_key - self.middle_key);
+        let reference_cents = scale.cents(reference_degree);
+        (0..MIDI_KEYS as i64)
+            .map(|key| {
+                self.degree(key, scale).map(|degree| {
+                    let cents = scale.cents(degree) - reference_cents;
+                    (self.reference_frequency * 2f64.powf(cents / 1200.0)) as f32
+                })
+            })
+            .collect()
+    }
+}
@@ -1,9 +1,71 @@
 use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 
 //rename to features
-#[derive(Debug, Clone, Copy, ValueEnum, PartialEq)]
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Serialize, Deserialize)]
 pub enum Visualizer {
     Freq,
     RMS,
     WaveForm,
+    Spectrum,
+    Spectrogram,
+    ///Detected pitch over the last seconds
+    PitchHistory,
+}
+
+impl fmt::Display for Visualizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Visualizer::Freq => "Frequency",
+            Visualizer::RMS => "RMS",
+            Visualizer::WaveForm => "Waveform",
+            Visualizer::Spectrum => "Spectrum",
+            Visualizer::Spectrogram => "Spectrogram",
+            Visualizer::PitchHistory => "Pitch history",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Visualizer {
+    ///The pitch detector only runs for the features showing a pitch
+    pub fn needs_pitch(self) -> bool {
+        matches!(self, Visualizer::Freq | Visualizer::PitchHistory)
+    }
+}
+
+///What the DSP computes at each hop. A single visualizer only needs its own features, a
+///dashboard showing several at once needs all of theirs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Features {
+    pub pitch: bool,
+    pub spectrum: bool,
+    ///Implies the spectrum, computed at every hop instead of only the last one
+    pub spectrogram: bool,
+}
+
+impl Features {
+    pub fn of(visualizers: impl IntoIterator<Item = Visualizer>) -> Self {
+        visualizers
+            .into_iter()
+            .fold(Features::default(), |features, visualizer| {
+                let other = Features::from(visualizer);
+                Features {
+                    pitch: features.pitch || other.pitch,
+                    spectrum: features.spectrum || other.spectrum,
+                    spectrogram: features.spectrogram || other.spectrogram,
+                }
+            })
+    }
+}
+
+impl From<Visualizer> for Features {
+    fn from(visualizer: Visualizer) -> Self {
+        Features {
+            pitch: visualizer.needs_pitch(),
+            spectrum: visualizer == Visualizer::Spectrum,
+            spectrogram: visualizer == Visualizer::Spectrogram,
+        }
+    }
 }
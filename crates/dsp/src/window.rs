@@ -0,0 +1,137 @@
+///Window sizes we offer for the time domain analysis (RMS, pitch, waveform)
+pub const WINDOW_SIZES: [usize; 5] = [1024, 2048, 4096, 8192, 16384];
+
+///The hop is the window divided by one of these : 50%, 75% or 87.5% of overlap
+pub const OVERLAPS: [usize; 3] = [2, 4, 8];
+
+//below this, we would run the analysis more often than it is worth
+const MIN_HOP: usize = 64;
+
+///The latest size samples of the stream, in a circular history that survives between updates.
+///A new analysis is due every hop samples, whatever the frame rate. The hop is at most half the
+///window, so two consecutive analyses always overlap by half or more
+pub struct SlidingWindow {
+    history: Vec<f32>,
+    write_pos: usize,
+    size: usize,
+    hop: usize,
+    //samples pushed since the last complete hop
+    since_hop: usize,
+    ordered: Vec<f32>,
+}
+
+impl SlidingWindow {
+    pub fn new(size: usize, hop: usize) -> Self {
+        let mut window = Self {
+            history: Vec::new(),
+            write_pos: 0,
+            size: 0,
+            hop: 0,
+            since_hop: 0,
+            ordered: Vec::new(),
+        };
+        window.resize(size, hop);
+        window
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn hop(&self) -> usize {
+        self.hop
+    }
+
+    ///Samples still missing to complete the next hop
+    pub fn until_hop(&self) -> usize {
+        self.hop - self.since_hop
+    }
+
+    ///Changes the window and hop sizes, the history restarts from silence
+    pub fn resize(&mut self, size: usize, hop: usize) {
+        self.size = size.clamp(WINDOW_SIZES[0], WINDOW_SIZES[WINDOW_SIZES.len() - 1]);
+        self.hop = hop.clamp(MIN_HOP, self.size / 2);
+        self.history = vec![0.0; self.size];
+        self.ordered = Vec::with_capacity(self.size);
+        self.write_pos = 0;
+        self.since_hop = 0;
+    }
+
+    ///Takes samples until the next hop : returns how many were taken, and whether a hop is
+    ///complete, so the caller can analyse the window right at that sample
+    pub fn push(&mut self, samples: &[f32]) -> (usize, bool) {
+        let taken = samples.len().min(self.hop - self.since_hop);
+        for &sample in &samples[..taken] {
+            self.history[self.write_pos] = sample;
+            self.write_pos = (self.write_pos + 1) % self.size;
+        }
+        self.since_hop += taken;
+        let complete = self.since_hop == self.hop;
+        if complete {
+            self.since_hop = 0;
+        }
+        (taken, complete)
+    }
+
+    ///The window, oldest sample first
+    pub fn samples(&mut self) -> &[f32] {
+        let (newest, oldest) = self.history.split_at(self.write_pos);
+        self.ordered.clear();
+        self.ordered.extend_from_slice(oldest);
+        self.ordered.extend_from_slice(newest);
+        &self.ordered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hop_is_clamped() {
+        let window = SlidingWindow::new(4096, 8);
+        assert_eq!(window.hop(), MIN_HOP);
+        let window = SlidingWindow::new(4096, 4096);
+        assert_eq!(window.hop(), 2048);
+        let window = SlidingWindow::new(4096, 1024);
+        assert_eq!((window.size(), window.hop()), (4096, 1024));
+    }
+
+    #[test]
+    fn resize_restarts_from_silence() {
+        let mut window = SlidingWindow::new(1024, 256);
+        window.push(&[1.0; 100]);
+        window.resize(2048, 512);
+        assert_eq!((window.size(), window.hop()), (2048, 512));
+        assert_eq!(window.until_hop(), 512);
+        assert!(window.samples().iter().all(|&s| s == 0.0));
+        //out of the sizes we offer
+        window.resize(100, 64);
+        assert_eq!(window.size(), WINDOW_SIZES[0]);
+        window.resize(1 << 20, 1024);
+        assert_eq!(window.size(), WINDOW_SIZES[WINDOW_SIZES.len() - 1]);
+    }
+
+    //one hop per hop samples, whatever the size of the chunks they come in
+    #[test]
+    fn hops_for_an_input() {
+        for chunk in [1, 100, 1024, 5000] {
+            let mut window = SlidingWindow::new(4096, 1024);
+            let samples: Vec<f32> = (0..10_000).map(|i| i as f32).collect();
+            let mut hops = 0;
+            for chunk in samples.chunks(chunk) {
+                let mut offset = 0;
+                while offset < chunk.len() {
+                    let (taken, complete) = window.push(&chunk[offset..]);
+                    offset += taken;
+                    hops += complete as usize;
+                }
+            }
+            assert_eq!(hops, 10_000 / 1024, "in chunks of {}", chunk);
+            assert_eq!(window.until_hop(), 1024 - 10_000 % 1024);
+            //the window holds the latest samples, oldest first
+            let latest: Vec<f32> = (10_000 - 4096..10_000).map(|i| i as f32).collect();
+            assert_eq!(window.samples(), &latest[..]);
+        }
+    }
+}
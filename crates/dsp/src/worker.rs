@@ -0,0 +1,119 @@
+use crate::{Analysis, DigitalSignalProcessor, Features, Visualizer};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use triple_buffer::{Input, Output, TripleBuffer};
+
+///Changes the ui asks for, applied by the worker between two updates
+pub type DspCommand = Box<dyn FnOnce(&mut DigitalSignalProcessor) + Send>;
+
+enum Message {
+    Configure(DspCommand),
+    Features(Features),
+}
+
+///Runs the DSP on its own thread : it drains the ringbuff as samples arrive, and publishes each
+///Analysis in a triple buffer. The ui reads the latest one without ever waiting for the DSP,
+///and the DSP never waits for the ui, so a slow frame skips no audio
+pub struct DspWorker {
+    messages: Sender<Message>,
+    output: Output<Analysis>,
+    features: Features,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DspWorker {
+    pub fn spawn(
+        mut dsp: DigitalSignalProcessor,
+        features: impl Into<Features>,
+    ) -> Result<Self, String> {
+        let features = features.into();
+        let (input, output) = TripleBuffer::new(&dsp.analysis()).split();
+        let (messages, receiver) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let thread = std::thread::Builder::new()
+            .name("dsp".to_string())
+            .spawn(move || Self::run(dsp, features, receiver, input, thread_running))
+            .map_err(|e| format!("Failed to spawn the dsp thread: {}", e))?;
+        Ok(Self {
+            messages,
+            output,
+            features,
+            running,
+            thread: Some(thread),
+        })
+    }
+
+    fn run(
+        mut dsp: DigitalSignalProcessor,
+        mut features: Features,
+        receiver: Receiver<Message>,
+        mut input: Input<Analysis>,
+        running: Arc<AtomicBool>,
+    ) {
+        //the ringbuff is polled every millisecond, so an empty poll is normal between two audio
+        //callbacks : an underrun is a whole hop waited for without a sample
+        let mut starved_since = Instant::now();
+        while running.load(Ordering::Relaxed) {
+            while let Ok(message) = receiver.try_recv() {
+                match message {
+                    Message::Configure(command) => command(&mut dsp),
+                    Message::Features(f) => features = f,
+                }
+            }
+
+            //the DSP analyses every hop, we only publish when a new analysis happened
+            if dsp.pending_samples() == 0 {
+                let (_, hop) = dsp.window_size();
+                let hop = Duration::from_secs_f32(hop as f32 / dsp.sample_rate.max(1.0));
+                if starved_since.elapsed() >= hop {
+                    starved_since = Instant::now();
+                    dsp.starved();
+                    input.write(dsp.analysis());
+                }
+                std::thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+            starved_since = Instant::now();
+            let hops = dsp.hops();
+            dsp.update(features);
+            if dsp.hops() != hops {
+                input.write(dsp.analysis());
+            }
+        }
+    }
+
+    ///Runs command on the DSP from its thread, before the next update
+    pub fn configure(&self, command: impl FnOnce(&mut DigitalSignalProcessor) + Send + 'static) {
+        let _ = self.messages.send(Message::Configure(Box::new(command)));
+    }
+
+    pub fn set_visualizer(&mut self, visualizer: Visualizer) {
+        self.set_features(visualizer.into());
+    }
+
+    pub fn set_features(&mut self, features: Features) {
+        if features != self.features {
+            self.features = features;
+            let _ = self.messages.send(Message::Features(features));
+        }
+    }
+
+    ///The most recent analysis published by the DSP thread
+    pub fn latest(&mut self) -> &Analysis {
+        self.output.read()
+    }
+}
+
+impl Drop for DspWorker {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
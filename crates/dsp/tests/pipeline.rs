@@ -0,0 +1,100 @@
+//The whole chain without a sound card : a backend plays into the ringbuff, the DSP drains it
+use audio::FileAudioBackend;
+use audio::GeneratorBackend;
+use audio::audio_bridge::AudioBridge;
+use audio::backend::AudioBackend;
+use audio::generator::Signal;
+use dsp::{DigitalSignalProcessor, Visualizer};
+use std::time::{Duration, Instant};
+
+//Runs the DSP on what the backend plays until enough hops were analysed
+fn analyse(backend: &mut dyn AudioBackend, dsp: &mut DigitalSignalProcessor, hops: u64) {
+    dsp.sample_rate = backend.sample_rate();
+    backend.start().expect("the backend starts");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while dsp.hops() < hops {
+        assert!(Instant::now() < deadline, "the DSP got no samples");
+        std::thread::sleep(Duration::from_millis(1));
+        dsp.update(Visualizer::Freq);
+    }
+    backend.stop();
+}
+
+fn assert_a4(dsp: &DigitalSignalProcessor) {
+    let frequency = dsp.get_frequency().expect("a pitch is detected");
+    assert!(
+        (frequency - 440.0).abs() < 1.0,
+        "detected {} Hz instead of 440",
+        frequency
+    );
+    let note = dsp.get_note().expect("a note is detected");
+    assert_eq!(note.to_string(), "A4");
+}
+
+#[test]
+fn generator_to_dsp() {
+    let (bridge, producer) = AudioBridge::new();
+    let mut dsp = DigitalSignalProcessor::new(bridge.consumer);
+    let signal: Signal = "sine:440".parse().unwrap();
+    let mut backend = GeneratorBackend::new(producer, signal, 0.5, 48000.0, false);
+    analyse(&mut backend, &mut dsp, 8);
+    assert_a4(&dsp);
+}
+
+//A 44.1 kHz A4 at half scale, in a temporary file named after the test
+fn write_a440(name: &str, samples: usize) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("tuners-{}-{}.wav", name, std::process::id()));
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 44100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..samples {
+        let s = 0.5 * (std::f32::consts::TAU * 440.0 * i as f32 / 44100.0).sin();
+        writer.write_sample((s * i16::MAX as f32) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+#[test]
+fn wav_file_to_dsp() {
+    let path = write_a440("pipeline", 44100);
+    let (bridge, producer) = AudioBridge::new();
+    let mut dsp = DigitalSignalProcessor::new(bridge.consumer);
+    //not in real time, like --fast
+    let backend = FileAudioBackend::new(producer, &path, false);
+    let _ = std::fs::remove_file(&path);
+    let mut backend = backend.expect("the file is read back");
+    assert_eq!(backend.sample_rate(), 44100.0);
+    analyse(&mut backend, &mut dsp, 8);
+    assert_a4(&dsp);
+}
+
+#[test]
+fn fast_file_is_not_skipped() {
+    //ten windows of samples, read far faster than we drain them
+    let samples = 10 * 4096;
+    let path = write_a440("fast", samples);
+    let (bridge, producer) = AudioBridge::new();
+    let mut dsp = DigitalSignalProcessor::new(bridge.consumer);
+    dsp.set_live(false);
+    let backend = FileAudioBackend::new(producer, &path, false);
+    let _ = std::fs::remove_file(&path);
+    let mut backend = backend.expect("the file is read back");
+    dsp.sample_rate = backend.sample_rate();
+    backend.start().expect("the backend starts");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !backend.is_finished() {
+        assert!(Instant::now() < deadline, "the file never ended");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    dsp.update(Visualizer::Freq);
+    backend.stop();
+
+    let (_, hop) = dsp.window_size();
+    assert_eq!(dsp.dropped_samples(), 0);
+    assert_eq!(dsp.hops(), (samples / hop) as u64);
+}
@@ -0,0 +1,96 @@
+use egui::Color32;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+///Colour scales used to paint magnitudes, from quiet (0.0) to loud (1.0)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ColorMap {
+    Grayscale,
+    Inferno,
+    Viridis,
+    Magma,
+    Jet,
+}
+
+impl fmt::Display for ColorMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorMap::Grayscale => "Grayscale",
+            ColorMap::Inferno => "Inferno",
+            ColorMap::Viridis => "Viridis",
+            ColorMap::Magma => "Magma",
+            ColorMap::Jet => "Jet",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ColorMap {
+    pub const ALL: [ColorMap; 5] = [
+        ColorMap::Grayscale,
+        ColorMap::Inferno,
+        ColorMap::Viridis,
+        ColorMap::Magma,
+        ColorMap::Jet,
+    ];
+
+    //a few stops sampled from the reference maps, enough once linearly interpolated
+    fn stops(self) -> &'static [[u8; 3]] {
+        match self {
+            ColorMap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+            ColorMap::Inferno => &[
+                [0, 0, 4],
+                [40, 11, 84],
+                [101, 21, 110],
+                [159, 42, 99],
+                [212, 72, 66],
+                [245, 125, 21],
+                [250, 193, 39],
+                [252, 255, 164],
+            ],
+            ColorMap::Viridis => &[
+                [68, 1, 84],
+                [70, 50, 127],
+                [54, 92, 141],
+                [39, 127, 142],
+                [31, 161, 135],
+                [74, 194, 109],
+                [159, 218, 58],
+                [253, 231, 37],
+            ],
+            ColorMap::Magma => &[
+                [0, 0, 4],
+                [28, 16, 68],
+                [79, 18, 123],
+                [129, 37, 129],
+                [181, 54, 122],
+                [229, 80, 100],
+                [251, 135, 97],
+                [252, 253, 191],
+            ],
+            ColorMap::Jet => &[
+                [0, 0, 131],
+                [0, 60, 170],
+                [5, 255, 255],
+                [255, 255, 0],
+                [250, 0, 0],
+                [128, 0, 0],
+            ],
+        }
+    }
+
+    pub fn color(self, t: f32) -> Color32 {
+        let stops = self.stops();
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let frac = position - index as f32;
+        let (a, b) = (stops[index], stops[index + 1]);
+        let mix = |i: usize| (a[i] as f32 + (b[i] as f32 - a[i] as f32) * frac) as u8;
+        Color32::from_rgb(mix(0), mix(1), mix(2))
+    }
+
+    ///256 precomputed colours, to paint a whole texture without interpolating each pixel
+    pub fn lut(self) -> [Color32; 256] {
+        std::array::from_fn(|i| self.color(i as f32 / 255.0))
+    }
+}
@@ -0,0 +1,277 @@
+use crate::TunerApp;
+use dsp::Visualizer;
+use egui::{Color32, Rect, Sense, Stroke};
+use serde::{Deserialize, Serialize};
+
+//height of the bar on top of each pane, with its visualizer picker and split buttons
+const HEADER_HEIGHT: f32 = 22.0;
+//thickness of the handle between two panes
+const DIVIDER: f32 = 6.0;
+//a pane never gets smaller than this share of its split
+const MIN_RATIO: f32 = 0.1;
+
+pub const VISUALIZERS: [Visualizer; 6] = [
+    Visualizer::Freq,
+    Visualizer::RMS,
+    Visualizer::WaveForm,
+    Visualizer::Spectrum,
+    Visualizer::Spectrogram,
+    Visualizer::PitchHistory,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Direction {
+    ///Side by side
+    Horizontal,
+    ///One above the other
+    Vertical,
+}
+
+///The dashboard is a tree : each pane shows a visualizer, or is split in two panes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pane {
+    View(Visualizer),
+    Split {
+        direction: Direction,
+        ///Share of the space given to the first pane
+        ratio: f32,
+        first: Box<Pane>,
+        second: Box<Pane>,
+    },
+}
+
+impl Default for Pane {
+    //tuner and spectrum above, level and waveform under
+    fn default() -> Self {
+        let row = |first, second| Pane::Split {
+            direction: Direction::Horizontal,
+            ratio: 0.5,
+            first: Box::new(Pane::View(first)),
+            second: Box::new(Pane::View(second)),
+        };
+        Pane::Split {
+            direction: Direction::Vertical,
+            ratio: 0.6,
+            first: Box::new(row(Visualizer::Freq, Visualizer::Spectrum)),
+            second: Box::new(row(Visualizer::RMS, Visualizer::WaveForm)),
+        }
+    }
+}
+
+//what a pane asks its parent to do, from the buttons of its header
+enum Action {
+    Split(Direction),
+    Close,
+}
+
+impl Pane {
+    ///Every visualizer shown, for the DSP to compute all of them
+    pub fn visualizers(&self) -> Vec<Visualizer> {
+        match self {
+            Pane::View(visualizer) => vec![*visualizer],
+            Pane::Split { first, second, .. } => {
+                let mut visualizers = first.visualizers();
+                visualizers.extend(second.visualizers());
+                visualizers
+            }
+        }
+    }
+
+    ///Ratios out of what the dividers allow, from a hand edited storage, are brought back
+    pub fn sanitized(self) -> Self {
+        match self {
+            Pane::View(visualizer) => Pane::View(visualizer),
+            Pane::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => Pane::Split {
+                direction,
+                ratio: if ratio.is_finite() {
+                    ratio.clamp(MIN_RATIO, 1.0 - MIN_RATIO)
+                } else {
+                    0.5
+                },
+                first: Box::new(first.sanitized()),
+                second: Box::new(second.sanitized()),
+            },
+        }
+    }
+}
+
+//the two rects of a split and the divider between them
+fn split_rect(rect: Rect, direction: Direction, ratio: f32) -> (Rect, Rect, Rect) {
+    match direction {
+        Direction::Horizontal => {
+            let x = rect.left() + rect.width() * ratio;
+            (
+                Rect::from_min_max(rect.min, egui::pos2(x - DIVIDER / 2.0, rect.bottom())),
+                Rect::from_min_max(egui::pos2(x + DIVIDER / 2.0, rect.top()), rect.max),
+                Rect::from_min_max(
+                    egui::pos2(x - DIVIDER / 2.0, rect.top()),
+                    egui::pos2(x + DIVIDER / 2.0, rect.bottom()),
+                ),
+            )
+        }
+        Direction::Vertical => {
+            let y = rect.top() + rect.height() * ratio;
+            (
+                Rect::from_min_max(rect.min, egui::pos2(rect.right(), y - DIVIDER / 2.0)),
+                Rect::from_min_max(egui::pos2(rect.left(), y + DIVIDER / 2.0), rect.max),
+                Rect::from_min_max(
+                    egui::pos2(rect.left(), y - DIVIDER / 2.0),
+                    egui::pos2(rect.right(), y + DIVIDER / 2.0),
+                ),
+            )
+        }
+    }
+}
+
+impl TunerApp {
+    ///Fills the central panel with the panes of the dashboard
+    pub fn render_dashboard(&mut self, ui: &mut egui::Ui) {
+        let size = ui.available_size();
+        let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+        //the tree is taken out of self for the panes to draw with &mut self
+        let mut root = std::mem::replace(&mut self.dashboard, Pane::View(Visualizer::RMS));
+        //the last pane can split, but not close
+        if let Some(action) = self.show_pane(ui, &mut root, rect, egui::Id::new("dashboard")) {
+            Self::apply(&mut root, action);
+        }
+        self.dashboard = root;
+    }
+
+    //a split starts with the same visualizer on both sides
+    fn apply(pane: &mut Pane, action: Action) {
+        if let Action::Split(direction) = action
+            && let Pane::View(visualizer) = *pane
+        {
+            *pane = Pane::Split {
+                direction,
+                ratio: 0.5,
+                first: Box::new(Pane::View(visualizer)),
+                second: Box::new(Pane::View(visualizer)),
+            };
+        }
+    }
+
+    fn show_pane(
+        &mut self,
+        ui: &mut egui::Ui,
+        pane: &mut Pane,
+        rect: Rect,
+        id: egui::Id,
+    ) -> Option<Action> {
+        match pane {
+            Pane::View(visualizer) => self.show_view(ui, visualizer, rect, id),
+            Pane::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_rect, second_rect, divider) = split_rect(rect, *direction, *ratio);
+                let handle = ui.interact(divider, id.with("divider"), Sense::drag());
+                if let Some(pointer) = handle.interact_pointer_pos() {
+                    let position = match direction {
+                        Direction::Horizontal => (pointer.x - rect.left()) / rect.width(),
+                        Direction::Vertical => (pointer.y - rect.top()) / rect.height(),
+                    };
+                    *ratio = position.clamp(MIN_RATIO, 1.0 - MIN_RATIO);
+                }
+                if handle.hovered() || handle.dragged() {
+                    ui.ctx().set_cursor_icon(match direction {
+                        Direction::Horizontal => egui::CursorIcon::ResizeHorizontal,
+                        Direction::Vertical => egui::CursorIcon::ResizeVertical,
+                    });
+                }
+                let color = if handle.hovered() || handle.dragged() {
+                    Color32::from_gray(120)
+                } else {
+                    Color32::from_gray(50)
+                };
+                ui.painter().rect_filled(divider.shrink(1.0), 2.0, color);
+
+                let first_action = self.show_pane(ui, first, first_rect, id.with(1));
+                let second_action = self.show_pane(ui, second, second_rect, id.with(2));
+                //a closed pane leaves its whole space to the other one
+                match (first_action, second_action) {
+                    (Some(Action::Close), _) => *pane = (**second).clone(),
+                    (_, Some(Action::Close)) => *pane = (**first).clone(),
+                    (Some(action), _) => Self::apply(first, action),
+                    (_, Some(action)) => Self::apply(second, action),
+                    (None, None) => {}
+                }
+                None
+            }
+        }
+    }
+
+    fn show_view(
+        &mut self,
+        ui: &mut egui::Ui,
+        visualizer: &mut Visualizer,
+        rect: Rect,
+        id: egui::Id,
+    ) -> Option<Action> {
+        let header = Rect::from_min_max(
+            rect.min,
+            egui::pos2(rect.right(), rect.top() + HEADER_HEIGHT),
+        );
+        let body = Rect::from_min_max(egui::pos2(rect.left(), header.bottom()), rect.max);
+        let mut action = None;
+
+        let mut header_ui = ui.new_child(
+            egui::UiBuilder::new()
+                .max_rect(header.shrink2(egui::vec2(2.0, 1.0)))
+                .layout(egui::Layout::left_to_right(egui::Align::Center)),
+        );
+        header_ui.set_clip_rect(header);
+        egui::ComboBox::from_id_salt(id.with("visualizer"))
+            .selected_text(visualizer.to_string())
+            .show_ui(&mut header_ui, |ui| {
+                for v in VISUALIZERS {
+                    ui.selectable_value(visualizer, v, v.to_string());
+                }
+            });
+        header_ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            if ui.small_button("✕").on_hover_text("Close").clicked() {
+                action = Some(Action::Close);
+            }
+            if ui
+                .small_button("⬍")
+                .on_hover_text("Split top / bottom")
+                .clicked()
+            {
+                action = Some(Action::Split(Direction::Vertical));
+            }
+            if ui
+                .small_button("⬌")
+                .on_hover_text("Split side by side")
+                .clicked()
+            {
+                action = Some(Action::Split(Direction::Horizontal));
+            }
+        });
+
+        //the renderers paint where they are told, the clip keeps them in their pane
+        let mut body_ui = ui.new_child(egui::UiBuilder::new().max_rect(body));
+        body_ui.set_clip_rect(body.intersect(ui.clip_rect()));
+        match *visualizer {
+            Visualizer::RMS => self.render_rms_in_rect(&body_ui, body),
+            Visualizer::Freq => self.render_tuner_in_rect(&body_ui, body),
+            Visualizer::WaveForm => self.render_waveform_in_rect(&body_ui, body),
+            Visualizer::Spectrum => self.render_spectrum_in_rect(&body_ui, body),
+            Visualizer::Spectrogram => self.render_spectrogram_in_rect(&body_ui, body, id),
+            Visualizer::PitchHistory => self.render_pitch_history_in_rect(&body_ui, body),
+        }
+        ui.painter().rect_stroke(
+            rect,
+            0.0,
+            Stroke::new(1.0, Color32::from_gray(60)),
+            egui::StrokeKind::Inside,
+        );
+        action
+    }
+}
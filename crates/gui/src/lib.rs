@@ -1,6 +1,11 @@
+pub mod colormap;
+pub mod dashboard;
 pub mod panels;
 pub mod render;
+pub mod scope;
+pub mod settings;
 pub mod ui;
 
+pub use settings::Settings;
 pub use ui::DeviceType;
 pub use ui::TunerApp;
@@ -1,10 +1,20 @@
 use crate::TunerApp;
+use crate::render::SPECTROGRAM_VIEW;
+use crate::scope::{GAIN_RANGE, MS_PER_DIV};
+use crate::ui::DeviceType;
+use dsp::Ballistics;
+use dsp::PitchAxis;
+use dsp::TriggerMode;
 use dsp::Visualizer;
+use dsp::note::NOTE_NAMES;
+use dsp::{InstrumentTuning, Temperament, TuningSystem};
 
 impl TunerApp {
     pub fn central_panel(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
-            if self.audio_start {
+            if self.audio_start && self.dashboard_shown() {
+                self.render_dashboard(ui);
+            } else if self.audio_start {
                 match self.visualizer {
                     Visualizer::RMS => {
                         self.render_rms(ui);
@@ -15,6 +25,15 @@ impl TunerApp {
                     Visualizer::WaveForm => {
                         self.render_waveform(ui);
                     }
+                    Visualizer::Spectrum => {
+                        self.render_spectrum(ui);
+                    }
+                    Visualizer::Spectrogram => {
+                        self.render_spectrogram(ui);
+                    }
+                    Visualizer::PitchHistory => {
+                        self.render_pitch_history(ui);
+                    }
                 }
             } else {
                 ui.vertical_centered(|ui| {
@@ -33,7 +52,55 @@ impl TunerApp {
             .show(ctx, |ui| {
                 self.start_microphone_button(ui);
                 ui.separator();
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    self.device_picker(ui);
+                    ui.separator();
+                    self.record_controls(ui);
+                    ui.separator();
+                }
                 self.features_button(ui);
+                ui.separator();
+                ui.checkbox(&mut self.dark_mode, "Dark mode");
+                ui.checkbox(&mut self.show_stats, "Diagnostics");
+            });
+    }
+
+    ///Health of the audio path, over the visualizer : what the bridge and the DSP counted
+    pub fn stats_overlay(&mut self, ctx: &egui::Context) {
+        if !self.show_stats || !self.audio_start {
+            return;
+        }
+        let Some(stats) = &self.bridge_stats else {
+            return;
+        };
+        let stats = stats.snapshot();
+        egui::Area::new(egui::Id::new("stats_overlay"))
+            .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-12.0, 12.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.monospace(format!("callbacks   {}", stats.callbacks));
+                    ui.monospace(format!("pushed      {}", stats.pushed));
+                    let dropped = format!("dropped     {}", stats.dropped);
+                    if stats.dropped > 0 {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(255, 100, 100),
+                            egui::RichText::new(dropped).monospace(),
+                        );
+                    } else {
+                        ui.monospace(dropped);
+                    }
+                    ui.monospace(format!(
+                        "callback    {:?} (mean {:?}, max {:?})",
+                        stats.last_callback, stats.mean_callback, stats.max_callback
+                    ));
+                    ui.monospace(format!("ring fill   {} / {}", stats.fill, stats.capacity));
+                    ui.add(egui::ProgressBar::new(stats.fill_ratio()).desired_width(220.0));
+                    if let Some(analysis) = &self.analysis {
+                        ui.monospace(format!("dsp skipped {}", analysis.skipped_samples));
+                        ui.monospace(format!("underruns   {}", analysis.underruns));
+                    }
+                });
             });
     }
 
@@ -64,11 +131,40 @@ impl TunerApp {
 
             #[cfg(not(target_arch = "wasm32"))]
             {
-                if ui.button("Start Microphone").clicked() {
+                let label = match &self.input {
+                    audio::InputSource::File { .. } => "Start Playback",
+                    audio::InputSource::Generator { .. } => "Start Generator",
+                    audio::InputSource::Device { .. } => "Start Microphone",
+                };
+                if ui.button(label).clicked() {
                     self.start_audio();
                 }
             }
         } else {
+            #[cfg(not(target_arch = "wasm32"))]
+            if let audio::InputSource::File { path, .. } = &self.input {
+                if ui.button("Stop Playback").clicked() {
+                    self.stop_audio();
+                    return;
+                }
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if self.backend.as_ref().is_some_and(|b| b.is_finished()) {
+                    ui.label(format!("Finished {}", name));
+                } else {
+                    ui.label(format!("Playing {}", name));
+                }
+                return;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let audio::InputSource::Generator { signal, .. } = &self.input {
+                let signal = signal.to_string();
+                if ui.button("Stop Generator").clicked() {
+                    self.stop_audio();
+                    return;
+                }
+                ui.label(format!("Generating {}", signal));
+                return;
+            }
             if ui.button("Stop Microphone").clicked() {
                 self.stop_audio();
             }
@@ -76,8 +172,165 @@ impl TunerApp {
         }
     }
 
+    //The recording follows the microphone : it stops with it, and can't start before it
+    #[cfg(not(target_arch = "wasm32"))]
+    fn record_controls(&mut self, ui: &mut egui::Ui) {
+        ui.label("Record to:");
+        ui.add_enabled(
+            self.recorder.is_none(),
+            egui::TextEdit::singleline(&mut self.record_path).hint_text("recording.wav"),
+        );
+        match &self.recorder {
+            Some(recorder) => {
+                let (duration, dropped) = (recorder.duration().as_secs_f32(), recorder.dropped());
+                if dropped > 0 {
+                    ui.colored_label(
+                        egui::Color32::from_rgb(255, 100, 100),
+                        format!("{} samples lost", dropped),
+                    );
+                }
+                if ui.button(format!("⏹ Stop ({:.1} s)", duration)).clicked() {
+                    self.stop_recording();
+                }
+            }
+            None => {
+                if ui
+                    .add_enabled(self.audio_start, egui::Button::new("⏺ Record"))
+                    .clicked()
+                {
+                    self.start_recording();
+                }
+            }
+        }
+        if let Some(error) = &self.record_error {
+            ui.colored_label(egui::Color32::from_rgb(255, 100, 100), error);
+        }
+    }
+
+    //Changing the device while the microphone runs restarts the audio on the new one
+    #[cfg(not(target_arch = "wasm32"))]
+    fn device_picker(&mut self, ui: &mut egui::Ui) {
+        use audio::{ChannelSelection, DeviceSelector, InputSource};
+
+        let selected = match &self.input {
+            InputSource::Device {
+                device: Some(selector),
+                ..
+            } => selector.to_string(),
+            InputSource::Device { device: None, .. } => "Default".to_string(),
+            InputSource::File { path, .. } => path.display().to_string(),
+            InputSource::Generator { signal, .. } => signal.to_string(),
+        };
+        let channel = match &self.input {
+            InputSource::Device { channel, .. } => *channel,
+            _ => ChannelSelection::Mix,
+        };
+        let mut choice = None;
+
+        ui.label("Input device:");
+        egui::ComboBox::from_id_salt("input_device")
+            .selected_text(selected)
+            .width(ui.available_width())
+            .show_ui(ui, |ui| {
+                let source = InputSource::Device {
+                    device: None,
+                    channel,
+                };
+                if ui
+                    .selectable_label(self.input == source, "Default")
+                    .clicked()
+                {
+                    choice = Some(source);
+                }
+                for device in &self.devices {
+                    let source = InputSource::Device {
+                        device: Some(device.selector()),
+                        channel,
+                    };
+                    let label = format!("{} ({})", device.name, device.host);
+                    if ui.selectable_label(self.input == source, label).clicked() {
+                        choice = Some(source);
+                    }
+                }
+                ui.separator();
+                for signal in [
+                    audio::generator::Signal::Sine(440.0),
+                    audio::generator::Signal::Saw(110.0),
+                    audio::generator::Signal::PinkNoise,
+                ] {
+                    let label = format!("Test signal: {}", signal);
+                    let source = InputSource::Generator {
+                        signal,
+                        amplitude: 0.5,
+                        sample_rate: 48000.0,
+                        realtime: true,
+                    };
+                    if ui.selectable_label(self.input == source, label).clicked() {
+                        choice = Some(source);
+                    }
+                }
+            });
+
+        //the channel list comes from what the selected device supports at most, the backend opens
+        //a config with enough channels when the default one has too few
+        if let InputSource::Device { device, channel } = &self.input {
+            let info = self
+                .devices
+                .iter()
+                .find(|info| DeviceSelector::matches(device.as_ref(), info));
+            let max_channels = info
+                .and_then(|info| info.channels.iter().max().copied())
+                .unwrap_or(2);
+            if max_channels > 1 {
+                let mut selection = *channel;
+                egui::ComboBox::from_id_salt("input_channel")
+                    .selected_text(selection.to_string())
+                    .width(ui.available_width())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut selection, ChannelSelection::Mix, "Mix");
+                        for n in 0..max_channels as usize {
+                            let value = ChannelSelection::Channel(n);
+                            ui.selectable_value(&mut selection, value, value.to_string());
+                        }
+                    });
+                if selection != *channel {
+                    choice = Some(InputSource::Device {
+                        device: device.clone(),
+                        channel: selection,
+                    });
+                }
+            }
+        }
+
+        if ui.button("Refresh devices").clicked() {
+            self.devices = audio::list_input_devices();
+        }
+
+        if let Some(source) = choice
+            && source != self.input
+        {
+            self.input = source;
+            if self.audio_start {
+                self.stop_audio();
+                self.start_audio();
+            }
+        }
+    }
+
     fn features_button(&mut self, ui: &mut egui::Ui) {
-        if self.audio_start {
+        if !self.audio_start {
+            return;
+        }
+        if matches!(self.ui_type, DeviceType::Desktop) {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.show_dashboard, false, "Single");
+                ui.selectable_value(&mut self.show_dashboard, true, "Dashboard");
+            });
+        }
+        //the settings of every visualizer on screen
+        let shown = self.visualizers_shown();
+        let any = |f: fn(&Visualizer) -> bool| shown.iter().any(f);
+        if !self.dashboard_shown() {
             ui.label("Visualizer:");
 
             if ui
@@ -100,7 +353,369 @@ impl TunerApp {
             {
                 self.visualizer = Visualizer::WaveForm;
             }
+
+            if ui
+                .selectable_label(matches!(self.visualizer, Visualizer::Spectrum), "Spectrum")
+                .clicked()
+            {
+                self.visualizer = Visualizer::Spectrum;
+            }
+
+            if ui
+                .selectable_label(
+                    matches!(self.visualizer, Visualizer::Spectrogram),
+                    "Spectrogram",
+                )
+                .clicked()
+            {
+                self.visualizer = Visualizer::Spectrogram;
+            }
+
+            if ui
+                .selectable_label(
+                    matches!(self.visualizer, Visualizer::PitchHistory),
+                    "Pitch history",
+                )
+                .clicked()
+            {
+                self.visualizer = Visualizer::PitchHistory;
+            }
+        }
+
+        if any(|v| matches!(v, Visualizer::RMS | Visualizer::WaveForm) || v.needs_pitch()) {
+            ui.separator();
+            self.window_settings(ui);
+        }
+        if any(|v| *v == Visualizer::PitchHistory) {
+            self.pitch_history_settings(ui);
+        }
+        if any(|v| v.needs_pitch()) {
+            self.pitch_settings(ui);
+        }
+        if any(|v| matches!(v, Visualizer::Spectrum | Visualizer::Spectrogram)) {
+            ui.separator();
+            self.spectrum_settings(ui);
+        }
+        if any(|v| *v == Visualizer::Spectrogram) {
+            self.spectrogram_settings(ui);
+        }
+        if any(|v| *v == Visualizer::RMS) {
+            ui.separator();
+            self.meter_settings(ui);
+        }
+        if any(|v| *v == Visualizer::WaveForm) {
+            ui.separator();
+            self.scope_settings(ui);
+        }
+    }
+
+    fn pitch_history_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("History:");
+        ui.add(
+            egui::Slider::new(&mut self.pitch_history.seconds, 2.0..=60.0)
+                .suffix(" s")
+                .logarithmic(true),
+        );
+        ui.horizontal(|ui| {
+            for axis in PitchAxis::ALL {
+                ui.selectable_value(&mut self.pitch_axis, axis, axis.to_string());
+            }
+        });
+        ui.separator();
+    }
+
+    fn window_settings(&mut self, ui: &mut egui::Ui) {
+        let (window, hop) = (self.analysis_window, self.analysis_hop);
+        let overlap = (window / hop.max(1)).max(1);
+
+        ui.label("Analysis window:");
+        egui::ComboBox::from_id_salt("analysis_window")
+            .selected_text(self.analysis_window.to_string())
+            .show_ui(ui, |ui| {
+                for size in dsp::window::WINDOW_SIZES {
+                    ui.selectable_value(&mut self.analysis_window, size, size.to_string());
+                }
+            });
+
+        //a hop of window / n means each sample is analysed n times
+        ui.label("Overlap:");
+        ui.horizontal(|ui| {
+            for (ratio, label) in [(2, "50%"), (4, "75%"), (8, "87.5%")] {
+                if ui.selectable_label(overlap == ratio, label).clicked() {
+                    self.analysis_hop = self.analysis_window / ratio;
+                }
+            }
+        });
+        if window != self.analysis_window {
+            self.analysis_hop = (self.analysis_window / overlap).max(1);
+        }
+
+        if window != self.analysis_window || hop != self.analysis_hop {
+            self.configure_dsp();
+        }
+    }
+
+    fn pitch_settings(&mut self, ui: &mut egui::Ui) {
+        let (algorithm, a4) = (self.pitch_algorithm, self.a4);
+
+        ui.label("Pitch detection:");
+        egui::ComboBox::from_id_salt("pitch_algorithm")
+            .selected_text(self.pitch_algorithm.to_string())
+            .show_ui(ui, |ui| {
+                for algorithm in dsp::PitchAlgorithm::ALL {
+                    ui.selectable_value(
+                        &mut self.pitch_algorithm,
+                        algorithm,
+                        algorithm.to_string(),
+                    );
+                }
+            });
+
+        ui.label("Reference A4:");
+        ui.add(
+            egui::Slider::new(&mut self.a4, dsp::note::A4_RANGE)
+                .step_by(0.1)
+                .fixed_decimals(1)
+                .suffix(" Hz"),
+        );
+        ui.horizontal(|ui| {
+            for preset in [415.0, 440.0, 442.0] {
+                if ui
+                    .selectable_label(self.a4 == preset, format!("{}", preset))
+                    .clicked()
+                {
+                    self.a4 = preset;
+                }
+            }
+        });
+
+        if algorithm != self.pitch_algorithm || a4 != self.a4 {
+            self.configure_dsp();
         }
+
+        self.tuning_settings(ui);
+        self.instrument_settings(ui);
+    }
+
+    fn instrument_settings(&mut self, ui: &mut egui::Ui) {
+        let instrument = self.instrument.clone();
+
+        ui.label("Instrument:");
+        let selected = match &self.instrument {
+            Some(instrument) => instrument.to_string(),
+            None => "Chromatic".to_string(),
+        };
+        egui::ComboBox::from_id_salt("instrument")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.instrument, None, "Chromatic");
+                for preset in InstrumentTuning::presets() {
+                    let selected = self.instrument.as_ref() == Some(preset);
+                    if ui.selectable_label(selected, preset.to_string()).clicked() {
+                        self.instrument = Some(preset.clone());
+                    }
+                }
+            });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.custom_strings)
+                    .hint_text("E2 A2 D3 G3 B3 E4")
+                    .desired_width(120.0),
+            );
+            let custom = InstrumentTuning::custom("Custom", &self.custom_strings);
+            let button = ui.add_enabled(custom.is_ok(), egui::Button::new("Custom"));
+            match custom {
+                Ok(custom) if button.clicked() => self.instrument = Some(custom),
+                Err(e) if !self.custom_strings.is_empty() => {
+                    button.on_disabled_hover_text(e);
+                }
+                _ => {}
+            }
+        });
+
+        if instrument != self.instrument {
+            self.configure_dsp();
+        }
+    }
+
+    fn tuning_settings(&mut self, ui: &mut egui::Ui) {
+        let tuning = self.tuning.clone();
+        let (mut temperament, mut key) = match &self.tuning {
+            TuningSystem::Temperament { temperament, key } => (Some(*temperament), *key),
+            TuningSystem::Scala { .. } => (None, 0),
+        };
+
+        ui.label("Tuning:");
+        egui::ComboBox::from_id_salt("temperament")
+            .selected_text(self.tuning.to_string())
+            .show_ui(ui, |ui| {
+                for t in Temperament::ALL {
+                    ui.selectable_value(&mut temperament, Some(t), t.to_string());
+                }
+            });
+        if temperament.is_some_and(|t| t != Temperament::Equal) {
+            egui::ComboBox::from_id_salt("tuning_key")
+                .selected_text(format!("Key: {}", NOTE_NAMES[key]))
+                .show_ui(ui, |ui| {
+                    for (index, name) in NOTE_NAMES.iter().enumerate() {
+                        ui.selectable_value(&mut key, index, *name);
+                    }
+                });
+        }
+        if let Some(temperament) = temperament {
+            self.tuning = TuningSystem::Temperament { temperament, key };
+        }
+
+        //wasm has no file system to read scales from
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.scala_path)
+                        .hint_text("scale.scl")
+                        .desired_width(120.0),
+                );
+                if ui.button("Load").clicked() {
+                    match self.load_scala() {
+                        Ok(tuning) => {
+                            self.tuning = tuning;
+                            self.tuning_error = None;
+                        }
+                        Err(e) => self.tuning_error = Some(e),
+                    }
+                }
+            });
+            if let Some(error) = &self.tuning_error {
+                ui.colored_label(egui::Color32::from_rgb(255, 100, 100), error);
+            }
+        }
+
+        if tuning != self.tuning {
+            self.configure_dsp();
+        }
+    }
+
+    //the .kbm is optional : "scale.scl" alone, or "scale.scl mapping.kbm"
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_scala(&self) -> Result<TuningSystem, String> {
+        let mut paths = self.scala_path.split_whitespace();
+        let scale = dsp::tuning::Scale::load(paths.next().ok_or("No .scl file given")?.as_ref())?;
+        let mapping = match paths.next() {
+            Some(path) => Some(dsp::tuning::KeyboardMapping::load(path.as_ref())?),
+            None => None,
+        };
+        Ok(TuningSystem::Scala { scale, mapping })
+    }
+
+    fn spectrum_settings(&mut self, ui: &mut egui::Ui) {
+        let (size, window) = (self.fft_size, self.fft_window);
+
+        ui.label("FFT size:");
+        egui::ComboBox::from_id_salt("fft_size")
+            .selected_text(self.fft_size.to_string())
+            .show_ui(ui, |ui| {
+                for size in dsp::spectrum::FFT_SIZES {
+                    ui.selectable_value(&mut self.fft_size, size, size.to_string());
+                }
+            });
+
+        ui.label("Window:");
+        egui::ComboBox::from_id_salt("fft_window")
+            .selected_text(self.fft_window.to_string())
+            .show_ui(ui, |ui| {
+                for window in dsp::WindowFunction::ALL {
+                    ui.selectable_value(&mut self.fft_window, window, window.to_string());
+                }
+            });
+
+        if size != self.fft_size || window != self.fft_window {
+            self.configure_dsp();
+        }
+    }
+
+    fn meter_settings(&mut self, ui: &mut egui::Ui) {
+        let ballistics = self.ballistics;
+        ui.label("Meter ballistics:");
+        ui.horizontal_wrapped(|ui| {
+            for b in Ballistics::ALL {
+                ui.selectable_value(&mut self.ballistics, b, b.to_string());
+            }
+        });
+        if ballistics != self.ballistics {
+            self.configure_dsp();
+        }
+    }
+
+    fn scope_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Trigger:");
+        ui.horizontal(|ui| {
+            for mode in TriggerMode::ALL {
+                ui.selectable_value(&mut self.scope.trigger.mode, mode, mode.to_string());
+            }
+        });
+        if matches!(
+            self.scope.trigger.mode,
+            TriggerMode::Rising | TriggerMode::Falling
+        ) {
+            ui.add(egui::Slider::new(&mut self.scope.trigger.level, -1.0..=1.0).text("level"));
+            ui.add(
+                egui::Slider::new(&mut self.scope.trigger.hysteresis, 0.0..=0.5).text("hysteresis"),
+            );
+        }
+
+        ui.label("Time base:");
+        egui::ComboBox::from_id_salt("ms_per_div")
+            .selected_text(format!("{} ms/div", self.scope.ms_per_div))
+            .show_ui(ui, |ui| {
+                for ms in MS_PER_DIV {
+                    ui.selectable_value(&mut self.scope.ms_per_div, ms, format!("{} ms/div", ms));
+                }
+            });
+        ui.add(
+            egui::Slider::new(&mut self.scope.gain, GAIN_RANGE)
+                .text("gain")
+                .prefix("x")
+                .logarithmic(true),
+        );
+
+        //a frozen trace can still be zoomed and moved in time, it keeps the whole window
+        let held = self.scope_hold.is_some();
+        if ui.selectable_label(held, "Hold").clicked() {
+            self.scope_hold = if held { None } else { self.analysis.clone() };
+        }
+    }
+
+    fn spectrogram_settings(&mut self, ui: &mut egui::Ui) {
+        ui.label("Frequency axis:");
+        ui.horizontal(|ui| {
+            for axis in [dsp::FrequencyAxis::Linear, dsp::FrequencyAxis::Log] {
+                ui.selectable_value(&mut self.spectrogram_axis, axis, axis.to_string());
+            }
+        });
+
+        ui.label("Colours:");
+        egui::ComboBox::from_id_salt("colormap")
+            .selected_text(self.colormap.to_string())
+            .show_ui(ui, |ui| {
+                for map in crate::colormap::ColorMap::ALL {
+                    ui.selectable_value(&mut self.colormap, map, map.to_string());
+                }
+            });
+
+        ui.label("dB range:");
+        ui.add(
+            egui::Slider::new(&mut self.spectrogram_max_db, -60.0..=0.0)
+                .text("ceiling")
+                .suffix(" dB"),
+        );
+        ui.add(
+            egui::Slider::new(&mut self.spectrogram_min_db, -160.0..=-20.0)
+                .text("floor")
+                .suffix(" dB"),
+        );
+        //keep at least 10 dB between floor and ceiling, or everything turns into one colour
+        self.spectrogram_min_db = self.spectrogram_min_db.min(self.spectrogram_max_db - 10.0);
     }
 
     pub fn mobile_visualizer(&mut self, ui: &mut egui::Ui) {
@@ -120,6 +735,15 @@ impl TunerApp {
             dsp::Visualizer::Freq => {
                 self.render_tuner_in_rect(ui, rect);
             }
+            dsp::Visualizer::Spectrum => {
+                self.render_spectrum_in_rect(ui, rect);
+            }
+            dsp::Visualizer::Spectrogram => {
+                self.render_spectrogram_in_rect(ui, rect, egui::Id::new(SPECTROGRAM_VIEW));
+            }
+            dsp::Visualizer::PitchHistory => {
+                self.render_pitch_history_in_rect(ui, rect);
+            }
         }
     }
 
@@ -1,63 +1,231 @@
 use crate::TunerApp;
+use crate::scope::{AMPLITUDE_DIVISIONS, TIME_DIVISIONS};
+use dsp::{PitchAxis, PitchPoint};
 
+use dsp::TriggerMode;
+use dsp::note::IN_TUNE_CENTS;
 use egui::Stroke;
 use egui::{Color32, Pos2, Rect};
+use std::collections::hash_map::Entry;
+
+//range displayed by the spectrum : below 20 Hz and 120 dB under full scale there is nothing to see
+const SPECTRUM_MIN_FREQ: f32 = 20.0;
+const SPECTRUM_MIN_DB: f32 = -120.0;
+
+//the tuner gauge shows +-50 cents, spread over +-60 degrees
+const GAUGE_CENTS: f32 = 50.0;
+const GAUGE_ANGLE: f32 = std::f32::consts::PI / 3.0;
+
+//id of the spectrogram of the single view, the panes of the dashboard use their own
+pub(crate) const SPECTROGRAM_VIEW: &str = "spectrogram";
+
+//x position of freq on a log axis going from min to max frequencies
+fn log_x(rect: Rect, freq: f32, min: f32, max: f32) -> f32 {
+    rect.left() + (freq / min).log10() / (max / min).log10() * rect.width()
+}
+
+//the level meter and the RMS history go from -60 dBFS to full scale
+const METER_MIN_DB: f32 = -60.0;
+const METER_SCALE: [f32; 8] = [0.0, -6.0, -12.0, -18.0, -24.0, -36.0, -48.0, -60.0];
+const METER_WIDTH: f32 = 130.0;
+
+fn meter_y(rect: Rect, db: f32) -> f32 {
+    rect.bottom() - ((db - METER_MIN_DB) / -METER_MIN_DB).clamp(0.0, 1.0) * rect.height()
+}
+
+fn db_y(rect: Rect, db: f32) -> f32 {
+    rect.bottom() - (1.0 - db / SPECTRUM_MIN_DB).clamp(0.0, 1.0) * rect.height()
+}
 
 impl TunerApp {
     pub fn render_rms(&mut self, ui: &mut egui::Ui) {
         let size = ui.available_size();
-        let width = size.x;
-        let height = size.y;
-
         let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
-        let painter = ui.painter();
+        self.render_rms_in_rect(ui, rect);
+    }
 
-        painter.rect_filled(rect, 0.0, Color32::from_gray(30));
+    //The RMS of each analysis scrolling on a dBFS scale, with the level meter on its right
+    pub fn render_rms_in_rect(&mut self, ui: &egui::Ui, rect: egui::Rect) {
+        let meter_width = METER_WIDTH.min(rect.width() / 2.0);
+        let (history, meter) = (
+            Rect::from_min_max(
+                rect.min,
+                Pos2::new(rect.right() - meter_width, rect.bottom()),
+            ),
+            Rect::from_min_max(Pos2::new(rect.right() - meter_width, rect.top()), rect.max),
+        );
+        self.render_meter_in_rect(ui, meter);
 
-        if self.rms_history.len() > width as usize {
-            self.rms_history.remove(0);
+        let painter = ui.painter();
+        painter.rect_filled(history, 0.0, Color32::from_gray(30));
+        let font = egui::FontId::proportional(11.0);
+        for db in METER_SCALE {
+            let y = meter_y(history, db);
+            painter.line_segment(
+                [Pos2::new(history.left(), y), Pos2::new(history.right(), y)],
+                Stroke::new(1.0, Color32::from_gray(50)),
+            );
+            painter.text(
+                Pos2::new(history.left() + 2.0, y),
+                egui::Align2::LEFT_BOTTOM,
+                format!("{} dBFS", db),
+                font.clone(),
+                Color32::from_gray(140),
+            );
         }
 
+        let width = history.width().max(1.0) as usize;
+        if self.rms_history.len() > width {
+            let excess = self.rms_history.len() - width;
+            self.rms_history.drain(..excess);
+        }
         let n = self.rms_history.len();
         for (i, &v) in self.rms_history.iter().enumerate() {
-            let bar_height = v * height;
-            let x = rect.right() - n as f32 + i as f32;
+            let x = history.right() - n as f32 + i as f32;
+            let y = meter_y(history, dsp::metering::to_db(v));
             painter.rect_filled(
-                Rect::from_min_max(
-                    Pos2::new(x, rect.bottom() - bar_height),
-                    Pos2::new(x + 1.0, rect.bottom()),
-                ),
+                Rect::from_min_max(Pos2::new(x, y), Pos2::new(x + 1.0, history.bottom())),
                 0.0,
                 Color32::from_rgb(0, 200, 0),
             );
         }
     }
 
-    pub fn render_rms_in_rect(&mut self, ui: &egui::Ui, rect: egui::Rect) {
+    //A level meter : the bar follows the chosen ballistics, with the RMS beside it, a peak hold
+    //line, a clip light latched until clicked, and the readings in dBFS under it
+    pub fn render_meter_in_rect(&mut self, ui: &egui::Ui, rect: egui::Rect) {
         let painter = ui.painter();
-        let width = rect.width();
-        let height = rect.height();
+        painter.rect_filled(rect, 0.0, Color32::from_gray(20));
+        let reading = self.analysis.as_ref().map(|a| a.meter).unwrap_or_default();
+        let font = egui::FontId::proportional(11.0);
+        let text = Color32::from_gray(200);
 
-        painter.rect_filled(rect, 0.0, Color32::from_gray(30));
+        let led = Rect::from_min_size(
+            rect.min + egui::vec2(4.0, 4.0),
+            egui::vec2(rect.width() - 8.0, 16.0),
+        );
+        let clipped = reading.clips > self.clips_seen;
+        let response = ui.interact(led, ui.id().with("clip"), egui::Sense::click());
+        if response.clicked() {
+            self.clips_seen = reading.clips;
+        }
+        painter.rect_filled(
+            led,
+            2.0,
+            if clipped {
+                Color32::from_rgb(230, 30, 30)
+            } else {
+                Color32::from_gray(50)
+            },
+        );
+        painter.text(
+            led.center(),
+            egui::Align2::CENTER_CENTER,
+            "CLIP",
+            font.clone(),
+            text,
+        );
 
-        if self.rms_history.len() > width as usize {
-            self.rms_history.remove(0);
+        let readout_height = 48.0;
+        let scale = Rect::from_min_max(
+            Pos2::new(rect.left() + 4.0, led.bottom() + 8.0),
+            Pos2::new(rect.right() - 4.0, rect.bottom() - readout_height),
+        );
+        if scale.height() < 20.0 {
+            return;
         }
+        //labels on the left, the level bar, then the thinner RMS bar
+        let label_width = 26.0;
+        let bar_width = ((scale.width() - label_width) * 0.6).max(4.0);
+        let level_bar = Rect::from_min_max(
+            Pos2::new(scale.left() + label_width, scale.top()),
+            Pos2::new(scale.left() + label_width + bar_width, scale.bottom()),
+        );
+        let rms_bar = Rect::from_min_max(
+            Pos2::new(level_bar.right() + 3.0, scale.top()),
+            Pos2::new(scale.right(), scale.bottom()),
+        );
+        painter.rect_filled(level_bar, 0.0, Color32::from_gray(35));
+        painter.rect_filled(rms_bar, 0.0, Color32::from_gray(35));
 
-        let n = self.rms_history.len();
-        for (i, &v) in self.rms_history.iter().enumerate() {
-            let bar_height = (v * height).clamp(0.0, height);
-            let x = rect.right() - n as f32 + i as f32;
+        //green, then yellow from -18 dBFS (the usual alignment level), red over -6
+        let level_y = meter_y(level_bar, reading.level_db);
+        for (from, to, color) in [
+            (METER_MIN_DB, -18.0, Color32::from_rgb(0, 200, 0)),
+            (-18.0, -6.0, Color32::from_rgb(230, 200, 0)),
+            (-6.0, 0.0, Color32::from_rgb(230, 40, 40)),
+        ] {
+            let top = meter_y(level_bar, to).max(level_y);
+            let bottom = meter_y(level_bar, from);
+            if top < bottom {
+                painter.rect_filled(
+                    Rect::from_min_max(
+                        Pos2::new(level_bar.left(), top),
+                        Pos2::new(level_bar.right(), bottom),
+                    ),
+                    0.0,
+                    color,
+                );
+            }
+        }
+        painter.rect_filled(
+            Rect::from_min_max(
+                Pos2::new(rms_bar.left(), meter_y(rms_bar, reading.rms_db)),
+                rms_bar.max,
+            ),
+            0.0,
+            Color32::from_rgb(0, 150, 255),
+        );
+        let hold_y = meter_y(level_bar, reading.hold_db);
+        painter.line_segment(
+            [
+                Pos2::new(level_bar.left(), hold_y),
+                Pos2::new(rms_bar.right(), hold_y),
+            ],
+            Stroke::new(2.0, Color32::WHITE),
+        );
 
-            painter.rect_filled(
-                Rect::from_min_max(
-                    Pos2::new(x, rect.bottom() - bar_height),
-                    Pos2::new(x + 1.0, rect.bottom()),
-                ),
-                0.0,
-                Color32::from_rgb(0, 200, 0),
+        for db in METER_SCALE {
+            let y = meter_y(scale, db);
+            painter.line_segment(
+                [
+                    Pos2::new(level_bar.left() - 4.0, y),
+                    Pos2::new(level_bar.left(), y),
+                ],
+                Stroke::new(1.0, Color32::from_gray(140)),
+            );
+            painter.text(
+                Pos2::new(level_bar.left() - 5.0, y),
+                egui::Align2::RIGHT_CENTER,
+                db.to_string(),
+                font.clone(),
+                Color32::from_gray(140),
             );
         }
+        //where the scale of a VU or a PPM has its zero
+        let ballistics = self.ballistics;
+        if ballistics != dsp::Ballistics::Digital {
+            let y = meter_y(scale, ballistics.reference_db());
+            painter.line_segment(
+                [
+                    Pos2::new(level_bar.left(), y),
+                    Pos2::new(level_bar.right(), y),
+                ],
+                Stroke::new(1.0, Color32::from_rgb(255, 160, 0)),
+            );
+        }
+
+        let readout = format!(
+            "{} {:.1}\nTP {:.1}  PK {:.1}\nRMS {:.1} dBFS",
+            ballistics, reading.level_db, reading.true_peak_db, reading.peak_db, reading.rms_db
+        );
+        painter.text(
+            Pos2::new(rect.left() + 4.0, rect.bottom() - 4.0),
+            egui::Align2::LEFT_BOTTOM,
+            readout,
+            font,
+            text,
+        );
     }
 
     pub fn render_waveform(&mut self, ui: &mut egui::Ui) {
@@ -66,39 +234,156 @@ impl TunerApp {
         self.render_waveform_in_rect(ui, rect);
     }
 
+    //An oscilloscope : the trace starts at the trigger, so a periodic signal stands still. When
+    //nothing triggers it runs free on the latest samples, like the auto mode of a scope
     pub fn render_waveform_in_rect(&mut self, ui: &egui::Ui, rect: egui::Rect) {
         let painter = ui.painter();
-        let width = rect.width();
-        let height = rect.height();
-
+        let scope = self.scope;
         painter.rect_filled(rect, 0.0, Color32::from_gray(30));
 
-        let center_y = rect.center().y;
-        painter.line_segment(
-            [
-                Pos2::new(rect.left(), center_y),
-                Pos2::new(rect.right(), center_y),
-            ],
-            Stroke::new(1.0, Color32::from_gray(60)),
-        );
+        let grid = Stroke::new(1.0, Color32::from_gray(50));
+        let axis = Stroke::new(1.0, Color32::from_gray(80));
+        let font = egui::FontId::proportional(11.0);
+        let text = Color32::from_gray(160);
+        //labels on every division when there is room, every other one otherwise
+        let step = if rect.width() / TIME_DIVISIONS as f32 > 45.0 {
+            1
+        } else {
+            2
+        };
+        let decimals = if scope.ms_per_div < 1.0 { 1 } else { 0 };
+        for i in 0..=TIME_DIVISIONS {
+            let x = rect.left() + i as f32 / TIME_DIVISIONS as f32 * rect.width();
+            painter.line_segment(
+                [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
+                grid,
+            );
+            if i > 0 && i < TIME_DIVISIONS && i % step == 0 {
+                painter.text(
+                    Pos2::new(x + 2.0, rect.bottom() - 2.0),
+                    egui::Align2::LEFT_BOTTOM,
+                    format!("{:.*} ms", decimals, i as f32 * scope.ms_per_div),
+                    font.clone(),
+                    text,
+                );
+            }
+        }
+        let per_div = scope.per_div();
+        for i in 0..=AMPLITUDE_DIVISIONS {
+            let y = rect.top() + i as f32 / AMPLITUDE_DIVISIONS as f32 * rect.height();
+            let divisions = (AMPLITUDE_DIVISIONS / 2) as f32 - i as f32;
+            let stroke = if divisions == 0.0 { axis } else { grid };
+            painter.line_segment(
+                [Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)],
+                stroke,
+            );
+            if i > 0 && i < AMPLITUDE_DIVISIONS && i % 2 == 0 {
+                painter.text(
+                    Pos2::new(rect.left() + 2.0, y),
+                    egui::Align2::LEFT_BOTTOM,
+                    format!("{:+.2}", divisions * per_div),
+                    font.clone(),
+                    text,
+                );
+            }
+        }
 
-        if let Some(dsp) = &self.dsp {
-            let samples = dsp.get_samples(width as usize);
+        let y_of = |value: f32| {
+            (rect.center().y - value * scope.gain * rect.height() / 2.0)
+                .clamp(rect.top(), rect.bottom())
+        };
+        let edge = matches!(
+            scope.trigger.mode,
+            TriggerMode::Rising | TriggerMode::Falling
+        );
+        if edge {
+            let y = y_of(scope.trigger.level);
+            let orange = Color32::from_rgb(255, 160, 0);
+            painter.line_segment(
+                [Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)],
+                Stroke::new(1.0, orange.gamma_multiply(0.4)),
+            );
+            painter.text(
+                Pos2::new(rect.right() - 2.0, y),
+                egui::Align2::RIGHT_BOTTOM,
+                "T",
+                font.clone(),
+                orange,
+            );
+        }
 
-            if samples.len() > 1 {
-                let mut points = Vec::new();
+        let Some(analysis) = self.scope_hold.as_ref().or(self.analysis.as_ref()) else {
+            return;
+        };
+        let samples = &analysis.samples;
+        let span = scope.span(analysis.sample_rate).max(2);
+        let period = analysis.frequency.map(|f| analysis.sample_rate / f);
+        let trigger = scope.trigger.find(samples, span, period);
+        let start = trigger.unwrap_or(samples.len().saturating_sub(span) as f32);
+        //when the window is shorter than the time base, the trace stops where the samples do
+        let available = samples.len() as f32 - 1.0 - start;
 
-                for (i, &sample) in samples.iter().enumerate() {
-                    let x = rect.left() + (i as f32 / samples.len() as f32) * width;
-                    let y = center_y - (sample * height * 0.4);
-                    points.push(Pos2::new(x, y));
+        let color = Color32::from_rgb(0, 200, 255);
+        let x_of = |offset: f32| rect.left() + offset / span as f32 * rect.width();
+        if span as f32 > rect.width() {
+            //more samples than pixels : each column is filled from the lowest to the highest of
+            //its samples, so a spike or a clipped sample never falls between two picked ones
+            let first = (start.floor() as usize).min(samples.len());
+            let end = (first + span).min(samples.len());
+            let columns = ((end - first) as f32 / span as f32 * rect.width()).round() as usize;
+            let envelope = dsp::analysis::envelope(&samples[first..end], columns);
+            let step = (end - first) as f32 / envelope.len().max(1) as f32;
+            let mut mesh = egui::Mesh::default();
+            for (i, e) in envelope.iter().enumerate() {
+                let x = x_of((i as f32 + 0.5) * step);
+                //at least a pixel high, or a flat stretch would vanish
+                mesh.colored_vertex(Pos2::new(x, y_of(e.max) - 0.5), color);
+                mesh.colored_vertex(Pos2::new(x, y_of(e.min) + 0.5), color);
+                if i > 0 {
+                    let v = 2 * i as u32;
+                    mesh.add_triangle(v - 2, v - 1, v);
+                    mesh.add_triangle(v - 1, v, v + 1);
                 }
-
-                painter.add(egui::Shape::line(
-                    points,
-                    Stroke::new(2.0, Color32::from_rgb(0, 200, 255)),
-                ));
             }
+            painter.add(mesh);
+        } else {
+            //zoomed in, a line through every sample, read between samples from the trigger
+            let mut points = Vec::with_capacity(span + 1);
+            for i in 0..=span {
+                let offset = i as f32;
+                if offset > available {
+                    break;
+                }
+                let value = dsp::trigger::interpolate(samples, start + offset);
+                points.push(Pos2::new(x_of(offset), y_of(value)));
+            }
+            if points.len() > 1 {
+                painter.add(egui::Shape::line(points, Stroke::new(2.0, color)));
+            }
+        }
+
+        let mut status = format!(
+            "{}  {} ms/div  {:.3} FS/div",
+            scope.trigger.mode, scope.ms_per_div, per_div
+        );
+        if scope.trigger.mode != TriggerMode::Off && trigger.is_none() {
+            status.push_str("  Auto");
+        }
+        painter.text(
+            Pos2::new(rect.right() - 4.0, rect.top() + 4.0),
+            egui::Align2::RIGHT_TOP,
+            status,
+            font.clone(),
+            Color32::from_gray(200),
+        );
+        if self.scope_hold.is_some() {
+            painter.text(
+                Pos2::new(rect.left() + 4.0, rect.top() + 4.0),
+                egui::Align2::LEFT_TOP,
+                "HOLD",
+                egui::FontId::proportional(14.0),
+                Color32::from_rgb(255, 80, 80),
+            );
         }
     }
 
@@ -112,36 +397,469 @@ impl TunerApp {
         let painter = ui.painter();
         painter.rect_filled(rect, 0.0, Color32::from_gray(30));
 
-        if let Some(dsp) = &self.dsp {
-            let center = rect.center();
+        let Some(dsp) = &self.analysis else {
+            return;
+        };
+
+        //the gauge is an arc over the pivot of the needle, the texts go under the pivot
+        let radius = (rect.width() * 0.4).min(rect.height() * 0.45);
+        let pivot = Pos2::new(rect.center().x, rect.top() + rect.height() * 0.08 + radius);
+        let point = |cents: f32, r: f32| {
+            let angle = (cents / GAUGE_CENTS).clamp(-1.0, 1.0) * GAUGE_ANGLE;
+            pivot + egui::vec2(angle.sin() * r, -angle.cos() * r)
+        };
+        let arc = |from: f32, to: f32, r: f32| -> Vec<Pos2> {
+            (0..=32)
+                .map(|i| point(from + (to - from) * i as f32 / 32.0, r))
+                .collect()
+        };
+
+        painter.add(egui::Shape::line(
+            arc(-IN_TUNE_CENTS, IN_TUNE_CENTS, radius * 0.9),
+            Stroke::new(radius * 0.2, Color32::from_rgb(0, 90, 40)),
+        ));
+        painter.add(egui::Shape::line(
+            arc(-GAUGE_CENTS, GAUGE_CENTS, radius),
+            Stroke::new(2.0, Color32::from_gray(120)),
+        ));
+        for cents in (-50..=50).step_by(10) {
+            let cents = cents as f32;
+            let length = if cents % 50.0 == 0.0 { 0.85 } else { 0.92 };
+            painter.line_segment(
+                [point(cents, radius), point(cents, radius * length)],
+                Stroke::new(2.0, Color32::from_gray(120)),
+            );
+        }
+        for cents in [-50.0, -25.0, 0.0, 25.0, 50.0] {
+            painter.text(
+                point(cents, radius * 1.1),
+                egui::Align2::CENTER_CENTER,
+                format!("{:+}", cents),
+                egui::FontId::proportional(12.0),
+                Color32::from_gray(150),
+            );
+        }
+
+        let text_y = pivot.y + radius * 0.1;
+        let big = (radius * 0.4).clamp(24.0, 120.0);
+        let small = (big / 3.0).max(12.0);
+
+        if let (Some(note), Some(freq)) = (dsp.get_note(), dsp.get_frequency()) {
+            let color = if note.in_tune() {
+                Color32::from_rgb(0, 255, 100)
+            } else if note.cents.abs() <= 15.0 {
+                Color32::from_rgb(255, 200, 0)
+            } else {
+                Color32::from_rgb(255, 70, 50)
+            };
+
+            painter.line_segment(
+                [pivot, point(note.cents, radius * 0.95)],
+                Stroke::new(3.0, color),
+            );
+            painter.circle_filled(pivot, 6.0, color);
+
+            painter.text(
+                Pos2::new(pivot.x, text_y),
+                egui::Align2::CENTER_TOP,
+                note.to_string(),
+                egui::FontId::proportional(big),
+                color,
+            );
+            painter.text(
+                Pos2::new(pivot.x, text_y + big * 1.1),
+                egui::Align2::CENTER_TOP,
+                format!("{:+.1} cents", note.cents),
+                egui::FontId::proportional(small * 1.3),
+                color,
+            );
+            painter.text(
+                Pos2::new(pivot.x, text_y + big * 1.1 + small * 1.8),
+                egui::Align2::CENTER_TOP,
+                format!("{:.1} Hz / {:.1} Hz", freq, note.target_frequency),
+                egui::FontId::proportional(small),
+                Color32::from_gray(200),
+            );
+
+            if let Some(clarity) = dsp.get_clarity() {
+                painter.text(
+                    Pos2::new(pivot.x, text_y + big * 1.1 + small * 3.2),
+                    egui::Align2::CENTER_TOP,
+                    format!("{} - clarity {:.0}%", dsp.pitch_algorithm, clarity * 100.0),
+                    egui::FontId::proportional(small * 0.8),
+                    Color32::from_gray(130),
+                );
+            }
+        } else {
+            painter.circle_filled(pivot, 6.0, Color32::from_gray(120));
+            painter.text(
+                Pos2::new(pivot.x, text_y),
+                egui::Align2::CENTER_TOP,
+                "Play a note...",
+                egui::FontId::proportional(big * 0.6),
+                Color32::from_gray(150),
+            );
+        }
+
+        //one box per string : green once tuned, outlined while it is the one played
+        if let Some(strings) = &dsp.strings {
+            let count = strings.tuning.strings.len();
+            let width = (rect.width() * 0.9 / count as f32).min(70.0);
+            let height = 32.0;
+            let left = rect.center().x - width * count as f32 / 2.0;
+            let top = rect.bottom() - height - 12.0;
+            for (i, &midi) in strings.tuning.strings.iter().enumerate() {
+                let cell = Rect::from_min_size(
+                    Pos2::new(left + i as f32 * width + 3.0, top),
+                    egui::vec2(width - 6.0, height),
+                );
+                let fill = if strings.tuned[i] {
+                    Color32::from_rgb(0, 110, 50)
+                } else {
+                    Color32::from_gray(50)
+                };
+                let stroke = if strings.current == Some(i) {
+                    Stroke::new(2.0, Color32::WHITE)
+                } else {
+                    Stroke::new(1.0, Color32::from_gray(90))
+                };
+                painter.rect(cell, 4.0, fill, stroke, egui::StrokeKind::Inside);
+                painter.text(
+                    cell.center(),
+                    egui::Align2::CENTER_CENTER,
+                    dsp::note::midi_name(midi),
+                    egui::FontId::proportional(14.0),
+                    Color32::from_gray(220),
+                );
+            }
+        }
+    }
+
+    pub fn render_spectrum(&mut self, ui: &mut egui::Ui) {
+        let size = ui.available_size();
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        self.render_spectrum_in_rect(ui, rect);
+    }
+
+    pub fn render_spectrum_in_rect(&mut self, ui: &egui::Ui, rect: egui::Rect) {
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, Color32::from_gray(30));
+
+        let Some(dsp) = &self.analysis else {
+            return;
+        };
+        let bins = dsp.get_spectrum();
+        if bins.len() < 2 {
+            return;
+        }
+        let nyquist = dsp.sample_rate / 2.0;
+        let bin_width = nyquist / (bins.len() - 1) as f32;
+        let grid = Stroke::new(1.0, Color32::from_gray(60));
+        let font = egui::FontId::proportional(12.0);
+
+        for freq in [
+            50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 20000.0,
+        ] {
+            if freq >= nyquist {
+                break;
+            }
+            let x = log_x(rect, freq, SPECTRUM_MIN_FREQ, nyquist);
+            painter.line_segment(
+                [Pos2::new(x, rect.top()), Pos2::new(x, rect.bottom())],
+                grid,
+            );
+            let label = if freq >= 1000.0 {
+                format!("{}k", freq / 1000.0)
+            } else {
+                format!("{}", freq)
+            };
+            painter.text(
+                Pos2::new(x + 2.0, rect.bottom() - 2.0),
+                egui::Align2::LEFT_BOTTOM,
+                label,
+                font.clone(),
+                Color32::from_gray(150),
+            );
+        }
+        for db in (SPECTRUM_MIN_DB as i32..0).step_by(20) {
+            let y = db_y(rect, db as f32);
+            painter.line_segment(
+                [Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)],
+                grid,
+            );
+            painter.text(
+                Pos2::new(rect.left() + 2.0, y),
+                egui::Align2::LEFT_BOTTOM,
+                format!("{} dB", db),
+                font.clone(),
+                Color32::from_gray(150),
+            );
+        }
+
+        //On a log axis, the low bins are wider than a pixel and the high ones narrower :
+        //we interpolate between bins in the first case and keep the loudest bin in the second,
+        //so no peak disappears between two pixels
+        let columns = rect.width().max(1.0) as usize;
+        let ratio = (nyquist / SPECTRUM_MIN_FREQ).powf(1.0 / columns as f32);
+        let mut points = Vec::with_capacity(columns);
+        for column in 0..columns {
+            let low = SPECTRUM_MIN_FREQ * ratio.powi(column as i32) / bin_width;
+            let high = low * ratio;
+            let first = low.floor() as usize;
+            let last = (high.floor() as usize).min(bins.len() - 1);
+            let db = if last > first {
+                bins[first + 1..=last]
+                    .iter()
+                    .copied()
+                    .fold(f32::MIN, f32::max)
+            } else {
+                let next = (first + 1).min(bins.len() - 1);
+                let t = low - first as f32;
+                bins[first.min(bins.len() - 1)] * (1.0 - t) + bins[next] * t
+            };
+            points.push(Pos2::new(rect.left() + column as f32, db_y(rect, db)));
+        }
+
+        painter.add(egui::Shape::line(
+            points,
+            Stroke::new(1.5, Color32::from_rgb(255, 180, 0)),
+        ));
+    }
+
+    pub fn render_spectrogram(&mut self, ui: &mut egui::Ui) {
+        let size = ui.available_size();
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        self.render_spectrogram_in_rect(ui, rect, egui::Id::new(SPECTROGRAM_VIEW));
+    }
 
-            if let Some(note) = dsp.get_note() {
-                if let Some(freq) = dsp.get_frequency() {
+    //Each frame is one column, newest on the right : we repaint the whole history in a texture,
+    //one row per pixel (up to 512) so the frequency axis keeps its resolution when resized.
+    //Each view id gets its own texture, so dashboard panes do not overwrite each other's
+    pub fn render_spectrogram_in_rect(&mut self, ui: &egui::Ui, rect: egui::Rect, id: egui::Id) {
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, Color32::from_gray(30));
+
+        let Some(dsp) = &self.analysis else {
+            return;
+        };
+        let frames = &dsp.spectrogram;
+        let Some(bins) = frames.back().map(|f| f.len()) else {
+            return;
+        };
+        if bins < 2 {
+            return;
+        }
+
+        let columns = dsp.spectrogram_capacity;
+        let rows = (rect.height() as usize).clamp(1, 512);
+        let nyquist = dsp.sample_rate / 2.0;
+        let bin_width = nyquist / (bins - 1) as f32;
+        let min_freq = match self.spectrogram_axis {
+            dsp::FrequencyAxis::Linear => 0.0,
+            dsp::FrequencyAxis::Log => SPECTRUM_MIN_FREQ,
+        };
+        //which bin each row shows, top row being the highest frequency
+        let row_bins: Vec<usize> = (0..rows)
+            .map(|row| {
+                let t = 1.0 - row as f32 / (rows - 1).max(1) as f32;
+                let freq = self.spectrogram_axis.frequency(t, min_freq, nyquist);
+                ((freq / bin_width).round() as usize).min(bins - 1)
+            })
+            .collect();
+
+        let lut = self.colormap.lut();
+        let range = self.spectrogram_max_db - self.spectrogram_min_db;
+        let mut image = egui::ColorImage::filled([columns, rows], lut[0]);
+        let offset = columns - frames.len().min(columns);
+        for (i, frame) in frames.iter().enumerate() {
+            for (row, &bin) in row_bins.iter().enumerate() {
+                let t = (frame[bin] - self.spectrogram_min_db) / range;
+                image.pixels[row * columns + offset + i] =
+                    lut[(t.clamp(0.0, 1.0) * 255.0) as usize];
+            }
+        }
+
+        //a view that was not drawn last frame was closed, its texture is freed
+        let frame = ui.ctx().cumulative_frame_nr();
+        self.spectrogram_textures
+            .retain(|_, (_, drawn)| *drawn + 1 >= frame);
+        let (texture, drawn) = match self.spectrogram_textures.entry(id) {
+            Entry::Occupied(entry) => {
+                let view = entry.into_mut();
+                view.0.set(image, egui::TextureOptions::LINEAR);
+                view
+            }
+            Entry::Vacant(entry) => entry.insert((
+                ui.ctx()
+                    .load_texture("spectrogram", image, egui::TextureOptions::LINEAR),
+                frame,
+            )),
+        };
+        *drawn = frame;
+        painter.image(
+            texture.id(),
+            rect,
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+
+        let font = egui::FontId::proportional(12.0);
+        for freq in [
+            100.0, 200.0, 500.0, 1000.0, 2000.0, 5000.0, 10000.0, 20000.0,
+        ] {
+            if freq >= nyquist {
+                break;
+            }
+            let t = match self.spectrogram_axis {
+                dsp::FrequencyAxis::Linear => freq / nyquist,
+                dsp::FrequencyAxis::Log => (freq / min_freq).log10() / (nyquist / min_freq).log10(),
+            };
+            let y = rect.bottom() - t * rect.height();
+            let label = if freq >= 1000.0 {
+                format!("{}k", freq / 1000.0)
+            } else {
+                format!("{}", freq)
+            };
+            painter.text(
+                Pos2::new(rect.left() + 2.0, y),
+                egui::Align2::LEFT_CENTER,
+                label,
+                font.clone(),
+                Color32::from_gray(220),
+            );
+        }
+    }
+
+    pub fn render_pitch_history(&mut self, ui: &mut egui::Ui) {
+        let size = ui.available_size();
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        self.render_pitch_history_in_rect(ui, rect);
+    }
+
+    pub fn render_pitch_history_in_rect(&mut self, ui: &egui::Ui, rect: egui::Rect) {
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, Color32::from_gray(30));
+        let grid = Stroke::new(1.0, Color32::from_gray(60));
+        let font = egui::FontId::proportional(12.0);
+
+        let points = self.pitch_history.points();
+        let seconds = self.pitch_history.seconds as f64;
+        let latest = self.pitch_history.latest();
+        let x = |time: f64| rect.right() - ((latest - time) / seconds) as f32 * rect.width();
+
+        //what a point is plotted on, and the range of the axis
+        let axis = self.pitch_axis;
+        let value = |p: &PitchPoint| match axis {
+            PitchAxis::Cents => p.note.map(|n| n.cents),
+            PitchAxis::Notes => p.semitones(),
+        };
+        let (min, max) = match axis {
+            PitchAxis::Cents => (-GAUGE_CENTS, GAUGE_CENTS),
+            PitchAxis::Notes => {
+                //the range follows the notes played, at least an octave
+                let (low, high) = points
+                    .iter()
+                    .filter_map(|p| p.semitones())
+                    .fold((f32::MAX, f32::MIN), |(low, high), v| {
+                        (low.min(v), high.max(v))
+                    });
+                let (low, high) = if low > high {
+                    (57.0, 69.0)
+                } else {
+                    (low.floor() - 1.0, high.ceil() + 1.0)
+                };
+                let missing = (12.0 - (high - low)).max(0.0) / 2.0;
+                (low - missing, high + missing)
+            }
+        };
+        let y = |v: f32| rect.bottom() - (v - min) / (max - min) * rect.height();
+
+        match self.pitch_axis {
+            PitchAxis::Cents => {
+                painter.rect_filled(
+                    Rect::from_x_y_ranges(rect.x_range(), y(IN_TUNE_CENTS)..=y(-IN_TUNE_CENTS)),
+                    0.0,
+                    Color32::from_rgb(0, 60, 30),
+                );
+                for cents in [-50, -25, -10, 0, 10, 25, 50] {
+                    let y = y(cents as f32);
+                    painter.line_segment(
+                        [Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)],
+                        grid,
+                    );
                     painter.text(
-                        center,
-                        egui::Align2::CENTER_CENTER,
-                        &note,
-                        egui::FontId::proportional(120.0),
-                        Color32::from_rgb(0, 255, 100),
+                        Pos2::new(rect.left() + 2.0, y),
+                        egui::Align2::LEFT_BOTTOM,
+                        format!("{:+}", cents),
+                        font.clone(),
+                        Color32::from_gray(150),
+                    );
+                }
+            }
+            PitchAxis::Notes => {
+                //with a lot of octaves on screen, only the Cs get a line
+                let every = if max - min > 36.0 { 12 } else { 1 };
+                for midi in (min.ceil() as i32..=max.floor() as i32)
+                    .filter(|m| m.rem_euclid(12) % every == 0)
+                {
+                    let y = y(midi as f32);
+                    painter.line_segment(
+                        [Pos2::new(rect.left(), y), Pos2::new(rect.right(), y)],
+                        grid,
                     );
-
                     painter.text(
-                        Pos2::new(center.x, center.y + 80.0),
-                        egui::Align2::CENTER_CENTER,
-                        format!("{:.1} Hz", freq),
-                        egui::FontId::proportional(40.0),
-                        Color32::from_gray(200),
+                        Pos2::new(rect.left() + 2.0, y),
+                        egui::Align2::LEFT_CENTER,
+                        dsp::note::midi_name(midi),
+                        font.clone(),
+                        Color32::from_gray(150),
                     );
                 }
+            }
+        }
+        for second in 1..seconds as i32 {
+            let x = x(latest - second as f64);
+            painter.line_segment(
+                [
+                    Pos2::new(x, rect.bottom() - 6.0),
+                    Pos2::new(x, rect.bottom()),
+                ],
+                grid,
+            );
+        }
+
+        //consecutive voiced analyses are joined, an unvoiced one leaves a gap. In cents, a new
+        //note starts a new line too, or going from +49 to -49 would cross the whole graph
+        let mut previous: Option<(Pos2, i32)> = None;
+        for point in points {
+            let (Some(v), Some(note)) = (value(point), point.note) else {
+                previous = None;
+                continue;
+            };
+            let pos = Pos2::new(x(point.time), y(v).clamp(rect.top(), rect.bottom()));
+            let color = if note.in_tune() {
+                Color32::from_rgb(0, 255, 100)
             } else {
-                painter.text(
-                    center,
-                    egui::Align2::CENTER_CENTER,
-                    "Play a note...",
-                    egui::FontId::proportional(60.0),
-                    Color32::from_gray(150),
-                );
+                Color32::from_rgb(255, 200, 0)
+            };
+            match previous {
+                Some((from, midi)) if midi == note.midi || self.pitch_axis == PitchAxis::Notes => {
+                    painter.line_segment([from, pos], Stroke::new(2.0, color));
+                }
+                _ => {
+                    painter.circle_filled(pos, 1.5, color);
+                    if self.pitch_axis == PitchAxis::Cents {
+                        painter.text(
+                            pos + egui::vec2(0.0, -6.0),
+                            egui::Align2::LEFT_BOTTOM,
+                            note.to_string(),
+                            font.clone(),
+                            Color32::from_gray(200),
+                        );
+                    }
+                }
             }
+            previous = Some((pos, note.midi));
         }
     }
 }
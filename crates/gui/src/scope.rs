@@ -0,0 +1,59 @@
+use dsp::Trigger;
+use serde::{Deserialize, Serialize};
+
+///Divisions of the grid, across and up
+pub const TIME_DIVISIONS: usize = 10;
+pub const AMPLITUDE_DIVISIONS: usize = 8;
+
+///Time bases offered, in milliseconds per division. The longest ones need a large analysis
+///window, the trace stops where the window ends
+pub const MS_PER_DIV: [f32; 9] = [0.1, 0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 50.0];
+
+pub const GAIN_RANGE: std::ops::RangeInclusive<f32> = 0.25..=64.0;
+
+///Settings of the oscilloscope the waveform view is drawn as
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scope {
+    pub trigger: Trigger,
+    pub ms_per_div: f32,
+    ///Vertical zoom : at 1, full scale fills the height
+    pub gain: f32,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self {
+            trigger: Trigger::default(),
+            ms_per_div: 2.0,
+            gain: 1.0,
+        }
+    }
+}
+
+impl Scope {
+    ///Samples shown across the screen
+    pub fn span(&self, sample_rate: f32) -> usize {
+        (self.ms_per_div * TIME_DIVISIONS as f32 / 1000.0 * sample_rate).round() as usize
+    }
+
+    ///Full scale per vertical division
+    pub fn per_div(&self) -> f32 {
+        2.0 / (self.gain * AMPLITUDE_DIVISIONS as f32)
+    }
+
+    pub fn sanitized(mut self) -> Self {
+        if !MS_PER_DIV.contains(&self.ms_per_div) {
+            self.ms_per_div = Scope::default().ms_per_div;
+        }
+        let (min, max) = (*GAIN_RANGE.start(), *GAIN_RANGE.end());
+        self.gain = if self.gain.is_finite() {
+            self.gain.clamp(min, max)
+        } else {
+            1.0
+        };
+        self.trigger.level = self.trigger.level.clamp(-1.0, 1.0);
+        self.trigger.hysteresis = self.trigger.hysteresis.clamp(0.0, 0.5);
+        self
+    }
+}
@@ -0,0 +1,109 @@
+use crate::colormap::ColorMap;
+use crate::dashboard::Pane;
+use crate::scope::Scope;
+#[cfg(not(target_arch = "wasm32"))]
+use audio::InputSource;
+use dsp::PitchAxis;
+use dsp::{
+    Ballistics, FrequencyAxis, InstrumentTuning, PitchAlgorithm, TuningSystem, Visualizer,
+    WindowFunction,
+};
+use serde::{Deserialize, Serialize};
+
+///What the app remembers from one session to the next. Missing fields take their default,
+///so a settings file written by an older version still loads
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub visualizer: Visualizer,
+    ///Several visualizers in panes instead of one, on desktop
+    pub show_dashboard: bool,
+    pub dashboard: Pane,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub input: InputSource,
+    pub pitch_algorithm: PitchAlgorithm,
+    pub a4: f32,
+    pub tuning: TuningSystem,
+    pub instrument: Option<InstrumentTuning>,
+    pub analysis_window: usize,
+    pub analysis_hop: usize,
+    pub fft_size: usize,
+    pub fft_window: WindowFunction,
+    pub spectrogram_axis: FrequencyAxis,
+    pub spectrogram_min_db: f32,
+    pub spectrogram_max_db: f32,
+    pub colormap: ColorMap,
+    pub scope: Scope,
+    pub ballistics: Ballistics,
+    pub pitch_axis: PitchAxis,
+    pub pitch_history_seconds: f32,
+    pub dark_mode: bool,
+    pub show_stats: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            visualizer: Visualizer::RMS,
+            show_dashboard: false,
+            dashboard: Pane::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            input: InputSource::default(),
+            pitch_algorithm: PitchAlgorithm::Yin,
+            a4: dsp::note::DEFAULT_A4,
+            tuning: TuningSystem::default(),
+            instrument: None,
+            analysis_window: 4096,
+            analysis_hop: 1024,
+            fft_size: 4096,
+            fft_window: WindowFunction::Hann,
+            spectrogram_axis: FrequencyAxis::Log,
+            spectrogram_min_db: -100.0,
+            spectrogram_max_db: -10.0,
+            colormap: ColorMap::Inferno,
+            scope: Scope::default(),
+            ballistics: Ballistics::Digital,
+            pitch_axis: PitchAxis::Cents,
+            pitch_history_seconds: 10.0,
+            dark_mode: true,
+            show_stats: false,
+        }
+    }
+}
+
+impl Settings {
+    ///Storage can be edited by hand or come from another version : what the ui could not have
+    ///chosen is brought back to the closest value it could
+    pub fn sanitized(mut self) -> Self {
+        let (min, max) = (*dsp::note::A4_RANGE.start(), *dsp::note::A4_RANGE.end());
+        self.a4 = if self.a4.is_finite() {
+            self.a4.clamp(min, max)
+        } else {
+            dsp::note::DEFAULT_A4
+        };
+        let default = Settings::default();
+        if !dsp::window::WINDOW_SIZES.contains(&self.analysis_window) {
+            self.analysis_window = default.analysis_window;
+        }
+        //the ui only offers 50%, 75% and 87.5% of overlap
+        if !dsp::window::OVERLAPS.contains(&(self.analysis_window / self.analysis_hop.max(1)))
+            || !self
+                .analysis_window
+                .is_multiple_of(self.analysis_hop.max(1))
+        {
+            self.analysis_hop = self.analysis_window / 4;
+        }
+        if !dsp::spectrum::FFT_SIZES.contains(&self.fft_size) {
+            self.fft_size = default.fft_size;
+        }
+        self.spectrogram_max_db = self.spectrogram_max_db.clamp(-60.0, 0.0);
+        self.spectrogram_min_db = self
+            .spectrogram_min_db
+            .clamp(-160.0, -20.0)
+            .min(self.spectrogram_max_db - 10.0);
+        self.pitch_history_seconds = self.pitch_history_seconds.clamp(2.0, 60.0);
+        self.dashboard = self.dashboard.sanitized();
+        self.scope = self.scope.sanitized();
+        self
+    }
+}
@@ -1,13 +1,30 @@
-use audio::audio_bridge::AudioBridge;
+use crate::colormap::ColorMap;
+use crate::dashboard::Pane;
+use crate::scope::Scope;
+use crate::settings::Settings;
+use audio::audio_bridge::{AudioBridge, BridgeStats};
 use audio::backend::AudioBackend;
-#[cfg(not(target_arch = "wasm32"))]
-use audio::backend::native;
 #[cfg(target_arch = "wasm32")]
 use audio::backend::wasm;
+#[cfg(not(target_arch = "wasm32"))]
+use audio::{InputDeviceInfo, InputSource, RecordTap, Recorder};
+use dsp::Analysis;
+use dsp::Ballistics;
 use dsp::DigitalSignalProcessor;
+#[cfg(not(target_arch = "wasm32"))]
+use dsp::DspWorker;
+use dsp::Features;
+use dsp::FrequencyAxis;
+use dsp::InstrumentTuning;
+use dsp::PitchAlgorithm;
+use dsp::TriggerMode;
+use dsp::TuningSystem;
 use dsp::Visualizer;
+use dsp::WindowFunction;
+use dsp::{PitchAxis, PitchHistory};
 use egui::FontId;
 use egui::TextStyle;
+use std::collections::HashMap;
 #[cfg(target_arch = "wasm32")]
 use web_sys;
 
@@ -18,12 +35,63 @@ pub enum DeviceType {
 
 pub struct TunerApp {
     pub dsp: Option<DigitalSignalProcessor>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub worker: Option<DspWorker>,
+    ///Results of the latest DSP update, what we render
+    pub analysis: Option<Analysis>,
+    pub bridge_stats: Option<BridgeStats>,
+    pub show_stats: bool,
+    pub dark_mode: bool,
     pub ui_type: DeviceType,
     #[cfg(not(target_arch = "wasm32"))]
-    backend: Option<native::NativeAudioBackend>,
+    pub backend: Option<Box<dyn AudioBackend>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub input: InputSource,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub devices: Vec<InputDeviceInfo>,
     pub visualizer: Visualizer,
+    pub show_dashboard: bool,
+    pub dashboard: Pane,
     pub audio_start: bool,
     pub rms_history: Vec<f32>,
+    pub pitch_history: PitchHistory,
+    pub pitch_axis: PitchAxis,
+    pub scope: Scope,
+    pub ballistics: Ballistics,
+    ///Clip count of the meter when its light was last cleared
+    pub clips_seen: u64,
+    ///Analysis the scope froze on, drawn instead of the live one
+    pub scope_hold: Option<Analysis>,
+    pub pitch_algorithm: PitchAlgorithm,
+    pub a4: f32,
+    pub tuning: TuningSystem,
+    ///None is the chromatic tuner
+    pub instrument: Option<InstrumentTuning>,
+    pub custom_strings: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub scala_path: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub tuning_error: Option<String>,
+    ///Copy of the input a recorder can be plugged into, while the audio runs
+    #[cfg(not(target_arch = "wasm32"))]
+    pub record_tap: Option<RecordTap>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub recorder: Option<Recorder>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub record_path: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub record_error: Option<String>,
+    pub analysis_window: usize,
+    pub analysis_hop: usize,
+    pub fft_size: usize,
+    pub fft_window: WindowFunction,
+    pub spectrogram_axis: FrequencyAxis,
+    pub spectrogram_min_db: f32,
+    pub spectrogram_max_db: f32,
+    pub colormap: ColorMap,
+    ///One texture per view of the spectrogram, as each pane has its own height, with the frame it
+    ///was last drawn in
+    pub spectrogram_textures: HashMap<egui::Id, (egui::TextureHandle, u64)>,
     #[cfg(target_arch = "wasm32")]
     pub audio_initializing: bool, //to fix for promise / future return
 }
@@ -31,7 +99,11 @@ pub struct TunerApp {
 ///at each frame, we update the dsp, and display panels.
 impl eframe::App for TunerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.set_visuals(egui::Visuals::dark());
+        ctx.set_visuals(if self.dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
         if self.audio_start {
             self.update_dsp();
         }
@@ -40,6 +112,7 @@ impl eframe::App for TunerApp {
                 self.source_code_panel(ctx);
                 self.control_panel(ctx);
                 self.central_panel(ctx);
+                self.stats_overlay(ctx);
             }
             DeviceType::Mobile => {
                 self.apply_styles(ctx);
@@ -61,28 +134,235 @@ impl eframe::App for TunerApp {
             ctx.request_repaint();
         }
     }
+
+    //eframe calls it on exit and every few seconds : a file on native, localStorage on wasm
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, SETTINGS_KEY, &self.settings());
+    }
 }
 
+const SETTINGS_KEY: &str = "settings";
+
 impl TunerApp {
     pub fn new(ui_type: DeviceType) -> Self {
+        let settings = Settings::default();
         Self {
             dsp: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            worker: None,
+            analysis: None,
+            bridge_stats: None,
+            show_stats: settings.show_stats,
+            dark_mode: settings.dark_mode,
             ui_type,
             #[cfg(not(target_arch = "wasm32"))]
             backend: None,
-            visualizer: Visualizer::RMS,
+            #[cfg(not(target_arch = "wasm32"))]
+            input: settings.input,
+            #[cfg(not(target_arch = "wasm32"))]
+            devices: audio::list_input_devices(),
+            visualizer: settings.visualizer,
+            show_dashboard: settings.show_dashboard,
+            dashboard: settings.dashboard,
             audio_start: false,
             rms_history: Vec::new(),
+            pitch_history: PitchHistory::new(settings.pitch_history_seconds),
+            pitch_axis: settings.pitch_axis,
+            scope: settings.scope,
+            ballistics: settings.ballistics,
+            clips_seen: 0,
+            scope_hold: None,
+            pitch_algorithm: settings.pitch_algorithm,
+            a4: settings.a4,
+            tuning: settings.tuning,
+            instrument: settings.instrument,
+            custom_strings: String::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            scala_path: String::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            tuning_error: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            record_tap: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            recorder: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            record_path: "recording.wav".to_string(),
+            #[cfg(not(target_arch = "wasm32"))]
+            record_error: None,
+            analysis_window: settings.analysis_window,
+            analysis_hop: settings.analysis_hop,
+            fft_size: settings.fft_size,
+            fft_window: settings.fft_window,
+            spectrogram_axis: settings.spectrogram_axis,
+            spectrogram_min_db: settings.spectrogram_min_db,
+            spectrogram_max_db: settings.spectrogram_max_db,
+            colormap: settings.colormap,
+            spectrogram_textures: HashMap::new(),
             #[cfg(target_arch = "wasm32")]
             audio_initializing: false,
         }
     }
 
+    ///Restores what the previous session saved, if anything
+    pub fn restore(&mut self, storage: Option<&dyn eframe::Storage>) {
+        let Some(storage) = storage else {
+            return;
+        };
+        if let Some(settings) = eframe::get_value::<Settings>(storage, SETTINGS_KEY) {
+            self.apply_settings(settings);
+        }
+    }
+
+    ///What save() stores, taken from the current state of the ui
+    pub fn settings(&self) -> Settings {
+        Settings {
+            visualizer: self.visualizer,
+            show_dashboard: self.show_dashboard,
+            dashboard: self.dashboard.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            input: self.input.clone(),
+            pitch_algorithm: self.pitch_algorithm,
+            a4: self.a4,
+            tuning: self.tuning.clone(),
+            instrument: self.instrument.clone(),
+            analysis_window: self.analysis_window,
+            analysis_hop: self.analysis_hop,
+            fft_size: self.fft_size,
+            fft_window: self.fft_window,
+            spectrogram_axis: self.spectrogram_axis,
+            spectrogram_min_db: self.spectrogram_min_db,
+            spectrogram_max_db: self.spectrogram_max_db,
+            colormap: self.colormap,
+            pitch_axis: self.pitch_axis,
+            scope: self.scope,
+            ballistics: self.ballistics,
+            pitch_history_seconds: self.pitch_history.seconds,
+            dark_mode: self.dark_mode,
+            show_stats: self.show_stats,
+        }
+    }
+
+    ///Takes the settings over, once sanitized, and hands them to the DSP if it runs
+    pub fn apply_settings(&mut self, settings: Settings) {
+        let settings = settings.sanitized();
+        self.visualizer = settings.visualizer;
+        self.show_dashboard = settings.show_dashboard;
+        self.dashboard = settings.dashboard;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.input = settings.input;
+        }
+        self.pitch_algorithm = settings.pitch_algorithm;
+        self.a4 = settings.a4;
+        self.tuning = settings.tuning;
+        self.instrument = settings.instrument;
+        self.analysis_window = settings.analysis_window;
+        self.analysis_hop = settings.analysis_hop;
+        self.fft_size = settings.fft_size;
+        self.fft_window = settings.fft_window;
+        self.spectrogram_axis = settings.spectrogram_axis;
+        self.spectrogram_min_db = settings.spectrogram_min_db;
+        self.spectrogram_max_db = settings.spectrogram_max_db;
+        self.colormap = settings.colormap;
+        self.pitch_axis = settings.pitch_axis;
+        self.scope = settings.scope;
+        self.ballistics = settings.ballistics;
+        self.pitch_history.seconds = settings.pitch_history_seconds;
+        self.dark_mode = settings.dark_mode;
+        self.show_stats = settings.show_stats;
+        self.configure_dsp();
+    }
+
+    ///The DSP is rebuilt at each start, so it gets the settings chosen in the ui from here.
+    ///On native it lives on the worker thread : the settings travel there in a closure
+    pub fn configure_dsp(&mut self) {
+        let (algorithm, a4, fft_size, fft_window) = (
+            self.pitch_algorithm,
+            self.a4,
+            self.fft_size,
+            self.fft_window,
+        );
+        let (instrument, tuning) = (self.instrument.clone(), self.tuning.clone());
+        let window = (self.analysis_window, self.analysis_hop);
+        let ballistics = self.ballistics;
+        let configure = move |dsp: &mut DigitalSignalProcessor| {
+            dsp.set_pitch_algorithm(algorithm);
+            if dsp.meter.ballistics() != ballistics {
+                dsp.meter.set_ballistics(ballistics);
+            }
+            dsp.set_a4(a4);
+            if dsp.window_size() != window {
+                dsp.set_window_size(window.0, window.1);
+            }
+            if dsp.instrument() != instrument.as_ref() {
+                dsp.set_instrument(instrument);
+            }
+            if *dsp.tuning() != tuning {
+                dsp.set_tuning(tuning);
+            }
+            if dsp.spectrum.size() != fft_size {
+                dsp.spectrum.set_size(fft_size);
+            }
+            if dsp.spectrum.window() != fft_window {
+                dsp.spectrum.set_window(fft_window);
+            }
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(worker) = &self.worker {
+            worker.configure(configure);
+            return;
+        }
+        if let Some(dsp) = &mut self.dsp {
+            configure(dsp);
+        }
+    }
+
+    ///The mobile layout has no room for a dashboard
+    pub fn dashboard_shown(&self) -> bool {
+        self.show_dashboard && matches!(self.ui_type, DeviceType::Desktop)
+    }
+
+    ///Every visualizer on screen : the panes of the dashboard, or the selected one
+    pub fn visualizers_shown(&self) -> Vec<Visualizer> {
+        if self.dashboard_shown() {
+            self.dashboard.visualizers()
+        } else {
+            vec![self.visualizer]
+        }
+    }
+
+    ///What the DSP computes, for the visualizers on screen
+    pub fn features(&self) -> Features {
+        let shown = self.visualizers_shown();
+        let mut features = Features::of(shown.iter().copied());
+        //the scope locks on the detected period
+        if self.scope.trigger.mode == TriggerMode::Pitch && shown.contains(&Visualizer::WaveForm) {
+            features.pitch = true;
+        }
+        features
+    }
+
+    //On native the DSP runs on its own thread, we only pick its latest results.
+    //On wasm we only have this thread, so the DSP runs here, once per frame
     pub fn update_dsp(&mut self) {
+        let features = self.features();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(worker) = &mut self.worker {
+            worker.set_features(features);
+            let analysis = worker.latest().clone();
+            self.rms_history.push(analysis.get_rms());
+            self.pitch_history.push(&analysis);
+            self.analysis = Some(analysis);
+            return;
+        }
         if let Some(dsp) = &mut self.dsp {
-            dsp.update(self.visualizer);
+            dsp.update(features);
             let rms = dsp.get_rms();
             self.rms_history.push(rms);
+            let analysis = dsp.analysis();
+            self.pitch_history.push(&analysis);
+            self.analysis = Some(analysis);
         } else {
             #[cfg(target_arch = "wasm32")]
             web_sys::console::log_1(&"DSP is None!".into());
@@ -98,29 +378,39 @@ impl TunerApp {
 
         //we set our ringbuff to contain 2 seconds of audio, sampled at SAMPLE_RATE
         let (bridge, producer) = AudioBridge::new();
-        self.dsp = Some(DigitalSignalProcessor::new(bridge.consumer));
-        match native::NativeAudioBackend::new(producer) {
-            Ok(mut backend) => {
-                let sample_rate = backend.sample_rate();
-                println!("Backend sample rate: {} Hz", sample_rate);
-
-                if let Some(ref mut dsp) = self.dsp {
-                    dsp.sample_rate = sample_rate;
-                }
-
-                match backend.start() {
-                    Ok(_) => {
-                        self.backend = Some(backend);
-                        self.audio_start = true;
-                        println!("Audio started successfully");
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to start audio: {}", e);
-                    }
+        let mut dsp = DigitalSignalProcessor::new(bridge.consumer);
+        dsp.set_live(self.input.is_live());
+        self.dsp = Some(dsp);
+        self.configure_dsp();
+        //the diagnostics and the record button only get the bridge once everything started
+        let result = self.input.open(producer).and_then(|mut backend| {
+            let sample_rate = backend.sample_rate();
+            println!("Backend sample rate: {} Hz", sample_rate);
+            let mut dsp = self.dsp.take().ok_or("The DSP is gone")?;
+            dsp.sample_rate = sample_rate;
+            backend
+                .start()
+                .map_err(|e| format!("Failed to start audio: {}", e))?;
+            match DspWorker::spawn(dsp, self.features()) {
+                Ok(worker) => Ok((backend, worker)),
+                Err(e) => {
+                    backend.stop();
+                    Err(e)
                 }
             }
+        });
+        match result {
+            Ok((backend, worker)) => {
+                self.worker = Some(worker);
+                self.backend = Some(backend);
+                self.bridge_stats = Some(bridge.stats);
+                self.record_tap = Some(bridge.tap);
+                self.audio_start = true;
+                println!("Audio started successfully");
+            }
             Err(e) => {
-                eprintln!("Failed to create audio backend: {}", e);
+                self.dsp = None;
+                eprintln!("Failed to start the audio: {}", e);
             }
         }
     }
@@ -155,6 +445,7 @@ impl TunerApp {
         );
 
         self.dsp = Some(DigitalSignalProcessor::new(bridge.consumer));
+        self.configure_dsp();
         web_sys::console::log_1(&"DSP created".into());
 
         wasm_bindgen_futures::spawn_local(async move {
@@ -193,6 +484,31 @@ impl TunerApp {
         web_sys::console::log_1(&"Audio marked as started".into());
     }
 
+    ///Records the input to record_path, at the sample rate of the running backend
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start_recording(&mut self) {
+        let (Some(tap), Some(backend)) = (&self.record_tap, &self.backend) else {
+            return;
+        };
+        let path = std::path::Path::new(&self.record_path);
+        match Recorder::start(tap, path, backend.sample_rate()) {
+            Ok(recorder) => {
+                self.recorder = Some(recorder);
+                self.record_error = None;
+            }
+            Err(e) => self.record_error = Some(e),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take()
+            && let Err(e) = recorder.stop()
+        {
+            self.record_error = Some(e);
+        }
+    }
+
     pub fn stop_audio(&mut self) {
         if !self.audio_start {
             return;
@@ -200,6 +516,8 @@ impl TunerApp {
 
         #[cfg(not(target_arch = "wasm32"))]
         {
+            self.stop_recording();
+            self.record_tap = None;
             if let Some(backend) = &mut self.backend {
                 backend.stop();
             }
@@ -210,9 +528,18 @@ impl TunerApp {
         #[cfg(target_arch = "wasm32")]
         web_sys::console::log_1(&"Wasm backend unplugged".into());
 
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.worker = None;
+        }
         self.dsp = None;
+        self.analysis = None;
+        self.bridge_stats = None;
         self.audio_start = false;
         self.rms_history.clear();
+        self.pitch_history.clear();
+        self.scope_hold = None;
+        self.clips_seen = 0;
     }
     pub fn apply_styles(&mut self, ctx: &egui::Context) {
         let mut style = (*ctx.style()).clone();
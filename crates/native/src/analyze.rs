@@ -0,0 +1,353 @@
+use crate::Args;
+use crate::output::{OutputFormat, Record, csv_field, json_string, optional};
+use dsp::metering::to_db;
+use dsp::tuning::TuningSystem;
+use dsp::{DigitalSignalProcessor, Loudness, Visualizer};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+//a note held for fewer analyses than this is a glitch, not a segment of the pitch track
+const MIN_SEGMENT_FRAMES: usize = 2;
+
+#[derive(clap::Args)]
+pub struct AnalyzeArgs {
+    #[arg(required = true, help = "WAV files to analyse")]
+    files: Vec<PathBuf>,
+    #[arg(
+        short,
+        long,
+        help = "Format of the summaries printed on stdout",
+        value_enum,
+        default_value_t = OutputFormat::Plain
+    )]
+    format: OutputFormat,
+    #[arg(
+        long,
+        help = "Also write every analysis of each file in this format, to <file>.frames.<ext>",
+        value_enum
+    )]
+    frames: Option<OutputFormat>,
+    #[arg(
+        long,
+        help = "Directory for the frame files, instead of next to each input",
+        requires = "frames"
+    )]
+    frames_dir: Option<PathBuf>,
+}
+
+//Notes held one after the other : what the pitch track looks like once read by a human
+struct Segment {
+    note: String,
+    start: f64,
+    end: f64,
+    frames: usize,
+}
+
+struct Summary {
+    file: PathBuf,
+    duration: f64,
+    sample_rate: f32,
+    frames: usize,
+    voiced: usize,
+    median_frequency: Option<f32>,
+    median_note: Option<String>,
+    //cents of each voiced analysis to its nearest note : mean, standard deviation, min, max
+    cents: Option<(f32, f32, f32, f32)>,
+    rms_db: f32,
+    peak_db: f32,
+    lufs: Option<f32>,
+    track: Vec<Segment>,
+}
+
+///Runs the DSP over whole files as fast as it can and prints a summary of each, without the
+///ringbuff : the samples are handed to the DSP one hop at a time, so no analysis is skipped
+pub fn run(args: &Args, analyze: &AnalyzeArgs, tuning: TuningSystem) -> Result<(), String> {
+    //two inputs with the same name in different directories would share one frame file
+    if let Some(format) = analyze.frames {
+        let mut taken = HashMap::new();
+        for path in &analyze.files {
+            let frames = frames_path(path, analyze.frames_dir.as_deref(), format);
+            if let Some(other) = taken.insert(frames.clone(), path) {
+                return Err(format!(
+                    "{} and {} would both write their frames to {}",
+                    other.display(),
+                    path.display(),
+                    frames.display()
+                ));
+            }
+        }
+    }
+    let mut stdout = io::stdout();
+    let mut failed = 0;
+    if analyze.format == OutputFormat::Csv {
+        writeln!(
+            stdout,
+            "file,duration,sample_rate,frames,voiced,median_frequency,median_note,cents_mean,cents_std,cents_min,cents_max,rms_dbfs,peak_dbfs,lufs"
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    for path in &analyze.files {
+        match analyze_file(args, analyze, tuning.clone(), path) {
+            Ok(summary) => summary
+                .write(analyze.format, &mut stdout)
+                .map_err(|e| e.to_string())?,
+            Err(e) => {
+                eprintln!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!(
+            "{} of {} files could not be analysed",
+            failed,
+            analyze.files.len()
+        ));
+    }
+    Ok(())
+}
+
+fn analyze_file(
+    args: &Args,
+    analyze: &AnalyzeArgs,
+    tuning: TuningSystem,
+    path: &Path,
+) -> Result<Summary, String> {
+    //the pitch is tracked on the downmix, the levels are measured on every channel
+    let data = audio::wav::read_wav_frames(path)?;
+    let samples = data.downmix();
+    let mut dsp = DigitalSignalProcessor::offline();
+    dsp.sample_rate = data.sample_rate;
+    args.configure_dsp(&mut dsp, tuning);
+
+    let mut frames_out = match analyze.frames {
+        Some(format) => {
+            let frames_path = frames_path(path, analyze.frames_dir.as_deref(), format);
+            let file = File::create(&frames_path)
+                .map_err(|e| format!("Failed to create {}: {}", frames_path.display(), e))?;
+            let mut out = BufWriter::new(file);
+            Record::write_header(format, &mut out).map_err(|e| e.to_string())?;
+            Some((format, out))
+        }
+        None => None,
+    };
+
+    let mut loudness = Loudness::new(data.sample_rate, data.channels as usize);
+    loudness.push(&data.interleaved);
+
+    let mut frames = 0;
+    let mut frequencies = Vec::new();
+    let mut cents = Vec::new();
+    let mut track: Vec<Segment> = Vec::new();
+    let mut voiced_before = false;
+    let (_, hop) = dsp.window_size();
+    for chunk in samples.chunks(hop) {
+        let hops = dsp.hops();
+        dsp.process(chunk, Visualizer::Freq);
+        if dsp.hops() == hops {
+            continue;
+        }
+        frames += 1;
+        let record = Record::from_dsp(&dsp);
+        if let Some((format, out)) = &mut frames_out {
+            record.write(*format, out).map_err(|e| e.to_string())?;
+        }
+
+        let (Some(frequency), Some(note)) = (record.frequency, record.note) else {
+            voiced_before = false;
+            continue;
+        };
+        frequencies.push(frequency);
+        cents.extend(record.cents);
+        //a silence or another note starts a new segment
+        match track.last_mut() {
+            Some(segment) if voiced_before && segment.note == note => {
+                segment.end = record.time;
+                segment.frames += 1;
+            }
+            _ => track.push(Segment {
+                note,
+                start: record.time,
+                end: record.time,
+                frames: 1,
+            }),
+        }
+        voiced_before = true;
+    }
+    track.retain(|segment| segment.frames >= MIN_SEGMENT_FRAMES);
+    if let Some((_, out)) = &mut frames_out {
+        out.flush().map_err(|e| e.to_string())?;
+    }
+
+    let median_frequency = median(&mut frequencies);
+    //the levels of the loudest channel, what a meter per channel would show
+    let channels = data.channels as usize;
+    let mut sums = vec![0.0f32; channels];
+    for frame in data.interleaved.chunks_exact(channels) {
+        for (sum, &s) in sums.iter_mut().zip(frame) {
+            *sum += s * s;
+        }
+    }
+    let rms = sums
+        .iter()
+        .map(|sum| (sum / samples.len().max(1) as f32).sqrt())
+        .fold(0.0, f32::max);
+    let peak = data
+        .interleaved
+        .iter()
+        .fold(0.0, |peak: f32, &s| peak.max(s.abs()));
+    Ok(Summary {
+        file: path.to_path_buf(),
+        duration: samples.len() as f64 / data.sample_rate as f64,
+        sample_rate: data.sample_rate,
+        frames,
+        voiced: frequencies.len(),
+        median_frequency,
+        median_note: median_frequency
+            .and_then(|f| dsp.nearest_note(f))
+            .map(|n| n.to_string()),
+        cents: cents_stats(&cents),
+        rms_db: to_db(rms),
+        peak_db: to_db(peak),
+        lufs: loudness.integrated(),
+        track,
+    })
+}
+
+//take.wav gives take.frames.csv, in the directory asked for or next to the input
+fn frames_path(path: &Path, dir: Option<&Path>, format: OutputFormat) -> PathBuf {
+    let extension = match format {
+        OutputFormat::Plain => "txt",
+        OutputFormat::Json => "jsonl",
+        OutputFormat::Csv => "csv",
+    };
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = format!("{}.frames.{}", stem, extension);
+    match dir {
+        Some(dir) => dir.join(name),
+        None => path.with_file_name(name),
+    }
+}
+
+fn median(values: &mut [f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[middle - 1] + values[middle]) / 2.0)
+    } else {
+        Some(values[middle])
+    }
+}
+
+fn cents_stats(cents: &[f32]) -> Option<(f32, f32, f32, f32)> {
+    if cents.is_empty() {
+        return None;
+    }
+    let n = cents.len() as f32;
+    let mean = cents.iter().sum::<f32>() / n;
+    let variance = cents.iter().map(|c| (c - mean).powi(2)).sum::<f32>() / n;
+    let min = cents.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = cents.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    Some((mean, variance.sqrt(), min, max))
+}
+
+impl Summary {
+    fn write(&self, format: OutputFormat, out: &mut dyn Write) -> io::Result<()> {
+        let cents = |i: usize| self.cents.map(|c| [c.0, c.1, c.2, c.3][i]);
+        match format {
+            OutputFormat::Plain => {
+                writeln!(out, "{}", self.file.display())?;
+                writeln!(
+                    out,
+                    "  {:.2} s at {} Hz, {} analyses, {} voiced",
+                    self.duration, self.sample_rate, self.frames, self.voiced
+                )?;
+                match (self.median_frequency, &self.median_note) {
+                    (Some(freq), Some(note)) => {
+                        writeln!(out, "  median pitch {:.2} Hz ({})", freq, note)?
+                    }
+                    _ => writeln!(out, "  no pitch detected")?,
+                }
+                if let Some((mean, std, min, max)) = self.cents {
+                    writeln!(
+                        out,
+                        "  cents mean {:+.1} std {:.1} min {:+.1} max {:+.1}",
+                        mean, std, min, max
+                    )?;
+                }
+                writeln!(
+                    out,
+                    "  rms {:.1} dBFS peak {:.1} dBFS loudness {} LUFS",
+                    self.rms_db,
+                    self.peak_db,
+                    optional(self.lufs, 1, "-")
+                )?;
+                if !self.track.is_empty() {
+                    let track: Vec<String> = self
+                        .track
+                        .iter()
+                        .map(|s| format!("{} {:.2}-{:.2}s", s.note, s.start, s.end))
+                        .collect();
+                    writeln!(out, "  track {}", track.join(", "))?;
+                }
+                Ok(())
+            }
+            OutputFormat::Json => {
+                let track: Vec<String> = self
+                    .track
+                    .iter()
+                    .map(|s| {
+                        format!(
+                            "{{\"note\":\"{}\",\"start\":{:.3},\"end\":{:.3}}}",
+                            s.note, s.start, s.end
+                        )
+                    })
+                    .collect();
+                writeln!(
+                    out,
+                    "{{\"file\":{},\"duration\":{:.3},\"sample_rate\":{},\"frames\":{},\"voiced\":{},\"median_frequency\":{},\"median_note\":{},\"cents_mean\":{},\"cents_std\":{},\"cents_min\":{},\"cents_max\":{},\"rms_dbfs\":{:.2},\"peak_dbfs\":{:.2},\"lufs\":{},\"track\":[{}]}}",
+                    json_string(&self.file.display().to_string()),
+                    self.duration,
+                    self.sample_rate,
+                    self.frames,
+                    self.voiced,
+                    optional(self.median_frequency, 3, "null"),
+                    self.median_note
+                        .as_ref()
+                        .map_or("null".to_string(), |n| format!("\"{}\"", n)),
+                    optional(cents(0), 2, "null"),
+                    optional(cents(1), 2, "null"),
+                    optional(cents(2), 2, "null"),
+                    optional(cents(3), 2, "null"),
+                    self.rms_db,
+                    self.peak_db,
+                    optional(self.lufs, 2, "null"),
+                    track.join(",")
+                )
+            }
+            OutputFormat::Csv => writeln!(
+                out,
+                "{},{:.3},{},{},{},{},{},{},{},{},{},{:.2},{:.2},{}",
+                csv_field(&self.file.display().to_string()),
+                self.duration,
+                self.sample_rate,
+                self.frames,
+                self.voiced,
+                optional(self.median_frequency, 3, ""),
+                self.median_note.as_deref().unwrap_or(""),
+                optional(cents(0), 2, ""),
+                optional(cents(1), 2, ""),
+                optional(cents(2), 2, ""),
+                optional(cents(3), 2, ""),
+                self.rms_db,
+                self.peak_db,
+                optional(self.lufs, 2, "")
+            ),
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use crate::Args;
+use crate::output::{OutputFormat, Record};
+use audio::Recorder;
+use audio::audio_bridge::AudioBridge;
+use dsp::tuning::TuningSystem;
+use dsp::{
+    Ballistics, DigitalSignalProcessor, Envelope, FrequencyAxis, MeterReading, StringTracker,
+    Visualizer,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+//columns of the compact waveform
+const WAVE_WIDTH: usize = 64;
+
+//columns of the level bar, from -60 dBFS to full scale
+const METER_WIDTH: usize = 40;
+
+//how often the ringbuff is drained, the display refreshes at --rate
+const POLL: Duration = Duration::from_millis(5);
+
+//clears the terminal line, so a readout refreshes in place instead of scrolling
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+///Prints the selected feature on stdout until the input ends or Ctrl-C
+pub fn run(args: &Args, tuning: TuningSystem) {
+    let (bridge, producer) = AudioBridge::new();
+    let stats = bridge.stats.clone();
+    let mut dsp = DigitalSignalProcessor::new(bridge.consumer);
+    let source = args.input_source();
+    dsp.set_live(source.is_live());
+    let mut backend = match source.open(producer) {
+        Ok(backend) => backend,
+        Err(e) => {
+            eprintln!("Failed to create audio backend: {}", e);
+            return;
+        }
+    };
+
+    dsp.sample_rate = backend.sample_rate();
+    args.configure_dsp(&mut dsp, tuning);
+
+    //records go to the file, or stdout where they replace the live display
+    let records = args.format != OutputFormat::Plain || args.output.is_some();
+    let mut out: Box<dyn Write> = match &args.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path.display(), e);
+                return;
+            }
+        },
+        None => Box::new(io::stdout()),
+    };
+    if let Err(e) = Record::write_header(args.format, &mut out) {
+        eprintln!("Failed to write the output: {}", e);
+        return;
+    }
+
+    //Ctrl-C (or a kill) only asks the loop to end, so the backend is stopped and the line is left clean
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = running.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_running.store(false, Ordering::Relaxed)) {
+        eprintln!("Failed to set the Ctrl-C handler: {}", e);
+    }
+
+    if let Err(e) = backend.start() {
+        eprintln!("Failed to start backend: {}", e);
+    }
+    let recorder = match &args.record {
+        Some(path) => match Recorder::start(&bridge.tap, path, backend.sample_rate()) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    //the tuner, the waveform and the meter refresh a single line, the other features scroll
+    let in_place = !records
+        && matches!(
+            args.visualizer,
+            Visualizer::Freq | Visualizer::WaveForm | Visualizer::RMS
+        );
+    //a record holds the pitch, so the pitch detector has to run
+    let feature = if records {
+        Visualizer::Freq
+    } else {
+        args.visualizer
+    };
+    let period = Duration::from_secs_f64(1.0 / args.rate as f64);
+    let mut stdout = std::io::stdout();
+    let mut last_stats = Instant::now();
+    let mut last_draw = Instant::now();
+    //like the worker, an underrun is a whole hop waited for without a sample
+    let mut starved_since = Instant::now();
+    'capture: while running.load(Ordering::Relaxed) {
+        std::thread::sleep(POLL);
+        //read before draining : a source that ended has pushed all it had, its tail included
+        let finished = backend.is_finished();
+        if dsp.pending_samples() > 0 {
+            starved_since = Instant::now();
+        } else if starved_since.elapsed() >= hop_duration(&dsp) {
+            starved_since = Instant::now();
+            dsp.starved();
+        }
+        //one hop at a time, so each analysis gets its record or its line, whatever the rate
+        while dsp.next_hop(feature) {
+            if records {
+                if let Err(e) = Record::from_dsp(&dsp).write(args.format, &mut out) {
+                    report_write_error(e);
+                    break 'capture;
+                }
+            } else if args.visualizer == Visualizer::PitchHistory {
+                //the terminal scrolls like the graph of the gui
+                println!("{:>9.3}s {}", dsp.time(), tuner_line(&dsp));
+            }
+        }
+        if finished {
+            break;
+        }
+        if args.stats && last_stats.elapsed() >= Duration::from_secs(1) {
+            last_stats = Instant::now();
+            if in_place {
+                print!("{}", CLEAR_LINE);
+                let _ = stdout.flush();
+            }
+            eprintln!(
+                "{} | dsp skipped {} | underruns {}",
+                stats.snapshot(),
+                dsp.dropped_samples(),
+                dsp.underruns()
+            );
+        }
+        if records {
+            //flushed as it comes, so a script reading the file follows along
+            if let Err(e) = out.flush() {
+                report_write_error(e);
+                break;
+            }
+            continue;
+        }
+        //--rate only paces the display, the analyses above all ran
+        if last_draw.elapsed() < period {
+            continue;
+        }
+        last_draw = Instant::now();
+        match args.visualizer {
+            Visualizer::RMS => {
+                print!(
+                    "{}{}",
+                    CLEAR_LINE,
+                    meter_line(&dsp.meter.reading(), dsp.meter.ballistics(), METER_WIDTH)
+                );
+            }
+            Visualizer::WaveForm => {
+                print!(
+                    "{}{} {:.3}",
+                    CLEAR_LINE,
+                    wave_line(&dsp.get_envelope(WAVE_WIDTH)),
+                    dsp.get_rms()
+                );
+            }
+            Visualizer::Freq => {
+                print!("{}{}", CLEAR_LINE, tuner_line(&dsp));
+            }
+            Visualizer::Spectrum => {
+                if let Some((freq, db)) = dsp.spectrum.peak(dsp.sample_rate) {
+                    println!("Peak: {:>8.1} Hz {:>6.1} dB", freq, db);
+                }
+            }
+            Visualizer::PitchHistory => {}
+            Visualizer::Spectrogram => {
+                println!(
+                    "{}",
+                    waterfall_line(dsp.get_spectrum(), dsp.sample_rate, 80)
+                );
+            }
+        }
+        let _ = stdout.flush();
+    }
+
+    backend.stop();
+    let _ = out.flush();
+    if let Some(recorder) = recorder {
+        eprintln!("{}", stop_recording(recorder));
+    }
+    if in_place {
+        println!();
+    }
+}
+
+//How long the DSP waits for a hop of samples
+fn hop_duration(dsp: &DigitalSignalProcessor) -> Duration {
+    let (_, hop) = dsp.window_size();
+    Duration::from_secs_f32(hop as f32 / dsp.sample_rate.max(1.0))
+}
+
+//A reader going away, like head does, is not an error
+fn report_write_error(e: io::Error) {
+    if e.kind() != io::ErrorKind::BrokenPipe {
+        eprintln!("Failed to write the output: {}", e);
+    }
+}
+
+//Finishes the WAV file, and says how long it is or why it failed
+pub(crate) fn stop_recording(recorder: Recorder) -> String {
+    let (duration, dropped) = (recorder.duration(), recorder.dropped());
+    let path = recorder.path().display().to_string();
+    match recorder.stop() {
+        Ok(()) => format!(
+            "Recorded {:.1} s to {} ({} samples lost)",
+            duration.as_secs_f32(),
+            path,
+            dropped
+        ),
+        Err(e) => e,
+    }
+}
+
+//Note, frequency, cents with a needle, and how confident the detector is
+fn tuner_line(dsp: &DigitalSignalProcessor) -> String {
+    let (Some(note), Some(freq)) = (dsp.get_note(), dsp.get_frequency()) else {
+        return format!(
+            "{:<4} {:>8} Hz {:>6} cents {}",
+            "--",
+            "-",
+            "-",
+            " ".repeat(41)
+        );
+    };
+    format!(
+        "{:<4} {:>8.2} Hz {:>+6.1} cents {} {:>3.0}%{}",
+        note.to_string(),
+        freq,
+        note.cents,
+        cents_meter(note.cents, 41),
+        dsp.get_clarity().unwrap_or(0.0) * 100.0,
+        strings_line(dsp.strings.as_ref())
+    )
+}
+
+//One line of blocks, from -1 at the bottom to +1 at the top
+//each column shows the extreme of its envelope farthest from zero, so spikes are not skipped
+fn wave_line(envelope: &[Envelope]) -> String {
+    const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    envelope
+        .iter()
+        .map(|e| {
+            let s = if e.max.abs() >= e.min.abs() {
+                e.max
+            } else {
+                e.min
+            };
+            let level = ((s.clamp(-1.0, 1.0) + 1.0) / 2.0 * (LEVELS.len() - 1) as f32).round();
+            LEVELS[level as usize]
+        })
+        .collect()
+}
+
+//The level bar with the ballistics, then the peak, true peak and RMS, and CLIP once anything
+//clipped since the start
+fn meter_line(reading: &MeterReading, ballistics: Ballistics, width: usize) -> String {
+    let filled = |db: f32| ((db + 60.0) / 60.0 * width as f32).clamp(0.0, width as f32) as usize;
+    let (level, hold) = (filled(reading.level_db), filled(reading.hold_db));
+    let bar: String = (0..width)
+        .map(|i| match i {
+            _ if i < level => '█',
+            _ if i + 1 == hold => '|',
+            _ => '·',
+        })
+        .collect();
+    format!(
+        "{} {:>6.1} {:<6} peak {:>6.1} true peak {:>6.1} rms {:>6.1} dBFS{}",
+        bar,
+        reading.level_db,
+        ballistics.to_string(),
+        reading.peak_db,
+        reading.true_peak_db,
+        reading.rms_db,
+        if reading.clips > 0 { "  CLIP" } else { "" }
+    )
+}
+
+//One line of a text waterfall : low frequencies on the left, on a log axis, louder is denser
+pub(crate) fn waterfall_line(bins: &[f32], sample_rate: f32, width: usize) -> String {
+    const SHADES: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+    if bins.len() < 2 || width < 2 {
+        return String::new();
+    }
+    let nyquist = sample_rate / 2.0;
+    let bin_width = nyquist / (bins.len() - 1) as f32;
+    (0..width)
+        .map(|column| {
+            let t = column as f32 / (width - 1) as f32;
+            let freq = FrequencyAxis::Log.frequency(t, 20.0, nyquist);
+            let bin = ((freq / bin_width).round() as usize).min(bins.len() - 1);
+            let level = ((bins[bin] + 100.0) / 90.0).clamp(0.0, 1.0);
+            SHADES[(level * (SHADES.len() - 1) as f32) as usize]
+        })
+        .collect()
+}
+
+//The strings of the instrument being tuned, the one played in brackets, a * once in tune
+pub(crate) fn strings_line(strings: Option<&StringTracker>) -> String {
+    let Some(strings) = strings else {
+        return String::new();
+    };
+    let names: Vec<String> = strings
+        .tuning
+        .strings
+        .iter()
+        .enumerate()
+        .map(|(i, &midi)| {
+            let name = format!(
+                "{}{}",
+                dsp::note::midi_name(midi),
+                if strings.tuned[i] { "*" } else { "" }
+            );
+            if strings.current == Some(i) {
+                format!("[{}]", name)
+            } else {
+                name
+            }
+        })
+        .collect();
+    format!("  {}", names.join(" "))
+}
+
+//A text needle : the middle is the target note, each side goes up to 50 cents
+pub(crate) fn cents_meter(cents: f32, width: usize) -> String {
+    let middle = width / 2;
+    let position = (middle as f32 + cents / 50.0 * middle as f32)
+        .round()
+        .clamp(0.0, (width - 1) as f32) as usize;
+    let needle = if cents.abs() <= dsp::note::IN_TUNE_CENTS {
+        'O'
+    } else {
+        '|'
+    };
+    (0..width)
+        .map(|i| match i {
+            _ if i == position => needle,
+            _ if i == middle => ':',
+            _ => '-',
+        })
+        .collect::<String>()
+}
@@ -0,0 +1,82 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+///$XDG_CONFIG_HOME/tuners/config.toml, or ~/.config/tuners/config.toml
+pub fn default_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("tuners").join("config.toml"))
+}
+
+///The command line with the options of the config file put in front of the real ones.
+///Each key is the long name of an option, so `a4 = 442` reads as `--a4 442` : the parser
+///validates the values, and the flags typed after override them
+pub fn command_line(command: &clap::Command) -> Result<Vec<OsString>, String> {
+    let mut argv: Vec<OsString> = std::env::args_os().collect();
+    let explicit = config_flag(&argv);
+    let path = match (&explicit, default_path()) {
+        (Some(path), _) => path.clone(),
+        (None, Some(path)) if path.exists() => path,
+        _ => return Ok(argv),
+    };
+    let options = options(&path, command)?;
+    let at = argv.len().min(1);
+    argv.splice(at..at, options);
+    Ok(argv)
+}
+
+//--config is read before clap runs, since it decides what clap gets to parse
+fn config_flag(argv: &[OsString]) -> Option<PathBuf> {
+    let mut args = argv.iter().skip(1);
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--" {
+            return None;
+        }
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+fn options(path: &Path, command: &clap::Command) -> Result<Vec<OsString>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let table: toml::Table = text
+        .parse()
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    let mut options = Vec::new();
+    for (key, value) in table {
+        let long = key.replace('_', "-");
+        let known = command
+            .get_arguments()
+            .any(|arg| arg.get_long() == Some(long.as_str()));
+        if !known || long == "config" {
+            return Err(format!("Unknown option \"{}\" in {}", key, path.display()));
+        }
+        let flag = OsString::from(format!("--{}", long));
+        match value {
+            //a flag is set by true, and left alone by false
+            toml::Value::Boolean(true) => options.push(flag),
+            toml::Value::Boolean(false) => {}
+            toml::Value::String(s) => options.extend([flag, s.into()]),
+            toml::Value::Integer(n) => options.extend([flag, n.to_string().into()]),
+            toml::Value::Float(x) => options.extend([flag, x.to_string().into()]),
+            _ => {
+                return Err(format!(
+                    "Option \"{}\" in {} must be a string, a number or a boolean",
+                    key,
+                    path.display()
+                ));
+            }
+        }
+    }
+    Ok(options)
+}
@@ -1,11 +1,22 @@
-use audio::NativeAudioBackend;
-use audio::audio_bridge::AudioBridge;
-use audio::backend::AudioBackend;
-use clap::{Parser, ValueEnum};
+use audio::generator::Signal;
+use audio::{ChannelSelection, DeviceSelector, InputSource};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use dsp::Ballistics;
 use dsp::DigitalSignalProcessor;
+use dsp::InstrumentTuning;
+use dsp::PitchAlgorithm;
 use dsp::Visualizer;
+use dsp::tuning::{KeyboardMapping, Scale, Temperament, TuningSystem};
 use gui::{DeviceType, TunerApp};
-use std::time::Duration;
+use output::OutputFormat;
+use std::path::PathBuf;
+
+mod analyze;
+mod cli;
+mod config;
+mod output;
+mod tui;
 
 //compile with cargo run -p tuners_native_gui
 
@@ -14,63 +25,417 @@ use std::time::Duration;
 enum Ui {
     Gui,
     Cli,
-    // Tui,
+    Tui,
 }
 
 #[derive(Parser)]
 #[command(name = "Tuners")]
 #[command(about = "A simple tuner and sound visualizer")]
 #[command(version, about, long_about = None)]
-struct Args {
+#[command(args_override_self = true)]
+pub(crate) struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[arg(short, long, help = "Select the Ui to launch", value_enum, default_value_t = Ui::Gui)]
     ui: Ui,
     #[arg(short, long, help = "Select feature", value_enum, default_value_t = Visualizer::RMS)]
     visualizer: Visualizer,
+    #[arg(
+        short,
+        long,
+        help = "Samples in the analysis window: 1024, 2048, 4096, 8192 or 16384",
+        value_parser = parse_window,
+        default_value_t = 4096
+    )]
+    window: usize,
+    #[arg(
+        long,
+        help = "Samples between two analyses: the window divided by 2, 4 or 8 (default 4)",
+        value_parser = parse_hop
+    )]
+    hop: Option<usize>,
+    #[arg(
+        short,
+        long,
+        help = "How the level meter follows the signal: digital peak, vu, ppm1 (DIN) or ppm2 (BBC / EBU)",
+        value_enum,
+        default_value_t = Ballistics::Digital
+    )]
+    ballistics: Ballistics,
+    #[arg(
+        short,
+        long,
+        help = "Pitch detection algorithm",
+        value_enum,
+        default_value_t = PitchAlgorithm::Yin
+    )]
+    pitch: PitchAlgorithm,
+    #[arg(
+        long,
+        help = "Reference frequency of A4 in Hz, from 400 to 480 (default 440; the gui starts from its saved value instead)",
+        value_parser = parse_a4
+    )]
+    a4: Option<f32>,
+    #[arg(
+        short,
+        long,
+        help = "Temperament the notes are tuned to",
+        value_enum,
+        default_value_t = Temperament::Equal
+    )]
+    temperament: Temperament,
+    #[arg(
+        short,
+        long,
+        help = "Key the temperament is built on: C, F#, Bb...",
+        value_parser = dsp::tuning::parse_key,
+        default_value = "C"
+    )]
+    key: usize,
+    #[arg(
+        long,
+        help = "Tune an instrument string by string: guitar, drop-d, dadgad, open-g, bass, bass5, bass6, ukulele, baritone-ukulele, violin, viola, cello, double-bass, or notes like E2,A2,D3"
+    )]
+    instrument: Option<InstrumentTuning>,
+    #[arg(long, help = "Tune to a Scala .scl scale instead of a temperament")]
+    scl: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Scala .kbm keyboard mapping for the scale",
+        requires = "scl"
+    )]
+    kbm: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        help = "Select the input device by name or index (see --list-devices)"
+    )]
+    device: Option<DeviceSelector>,
+    #[arg(
+        short,
+        long,
+        help = "Input channel to analyse, from 1, or mix to average all channels",
+        default_value_t = ChannelSelection::Mix
+    )]
+    channel: ChannelSelection,
+    #[arg(long, help = "List the available input devices and exit")]
+    list_devices: bool,
+    #[arg(short, long, help = "Analyse a WAV file instead of a live input")]
+    input: Option<PathBuf>,
+    #[arg(
+        short,
+        long,
+        help = "Analyse a synthetic signal: sine:440, square:220, saw:110, triangle:330, white, pink, chirp:100:2000:5, tones:440,660:0.5",
+        conflicts_with = "input"
+    )]
+    generate: Option<Signal>,
+    #[arg(
+        long,
+        help = "Amplitude of the generated signal",
+        default_value_t = 0.5
+    )]
+    amplitude: f32,
+    #[arg(
+        long,
+        help = "Sample rate of the generated signal",
+        default_value_t = 48000.0
+    )]
+    sample_rate: f32,
+    #[arg(
+        long,
+        help = "Play the input file or signal as fast as possible instead of in real time"
+    )]
+    fast: bool,
+    #[arg(
+        long,
+        help = "Print ring buffer and callback statistics every second, on stderr"
+    )]
+    stats: bool,
+    #[arg(
+        short,
+        long,
+        help = "Refreshes per second of the cli display, json / csv records follow every analysis",
+        value_parser = clap::value_parser!(u32).range(1..=1000),
+        default_value_t = 60
+    )]
+    rate: u32,
+    #[arg(
+        short,
+        long,
+        help = "Output of the cli: plain, or one json / csv record per analysis",
+        value_enum,
+        default_value_t = OutputFormat::Plain
+    )]
+    format: OutputFormat,
+    #[arg(
+        short,
+        long,
+        help = "Write the cli records to this file instead of stdout"
+    )]
+    output: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Record the input to this WAV file, from the start of the capture"
+    )]
+    record: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "TOML file of default options, read from ~/.config/tuners/config.toml if it exists. The flags typed override it",
+        value_name = "FILE"
+    )]
+    config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    ///Analyse WAV files as fast as possible and print a summary of each.
+    ///The tuning options go before the subcommand: tuners_native -p mcleod analyze take.wav
+    Analyze(analyze::AnalyzeArgs),
+}
+
+impl Args {
+    ///Applies the analysis options of the command line to a new DSP
+    fn configure_dsp(&self, dsp: &mut DigitalSignalProcessor, tuning: TuningSystem) {
+        dsp.set_pitch_algorithm(self.pitch);
+        dsp.set_a4(self.a4.unwrap_or(dsp::note::DEFAULT_A4));
+        dsp.set_tuning(tuning);
+        dsp.set_instrument(self.instrument.clone());
+        dsp.set_window_size(self.window, self.hop());
+        dsp.meter.set_ballistics(self.ballistics);
+    }
+
+    fn hop(&self) -> usize {
+        self.hop.unwrap_or(self.window / 4)
+    }
+
+    fn input_source(&self) -> InputSource {
+        if let Some(signal) = &self.generate {
+            return InputSource::Generator {
+                signal: signal.clone(),
+                amplitude: self.amplitude,
+                sample_rate: self.sample_rate,
+                realtime: !self.fast,
+            };
+        }
+        match &self.input {
+            Some(path) => InputSource::File {
+                path: path.clone(),
+                realtime: !self.fast,
+            },
+            None => InputSource::Device {
+                device: self.device.clone(),
+                channel: self.channel,
+            },
+        }
+    }
+
+    fn tuning(&self) -> Result<TuningSystem, String> {
+        let Some(scl) = &self.scl else {
+            return Ok(TuningSystem::Temperament {
+                temperament: self.temperament,
+                key: self.key,
+            });
+        };
+        let mapping = match &self.kbm {
+            Some(kbm) => Some(KeyboardMapping::load(kbm)?),
+            None => None,
+        };
+        Ok(TuningSystem::Scala {
+            scale: Scale::load(scl)?,
+            mapping,
+        })
+    }
+}
+
+fn parse_a4(s: &str) -> Result<f32, String> {
+    let a4: f32 = s
+        .parse()
+        .map_err(|_| format!("Invalid frequency \"{}\"", s))?;
+    if !dsp::note::A4_RANGE.contains(&a4) {
+        return Err(format!("A4 must be between 400 and 480 Hz, got {}", a4));
+    }
+    Ok(a4)
+}
+
+fn parse_window(s: &str) -> Result<usize, String> {
+    let size: usize = s
+        .parse()
+        .map_err(|_| format!("Invalid window size \"{}\"", s))?;
+    if !dsp::window::WINDOW_SIZES.contains(&size) {
+        return Err(format!(
+            "The window must be one of {:?}, got {}",
+            dsp::window::WINDOW_SIZES,
+            size
+        ));
+    }
+    Ok(size)
+}
+
+//the hop alone can only be checked against every window, main checks it against --window
+fn parse_hop(s: &str) -> Result<usize, String> {
+    let hop: usize = s.parse().map_err(|_| format!("Invalid hop \"{}\"", s))?;
+    let valid = dsp::window::WINDOW_SIZES
+        .iter()
+        .any(|&size| dsp::window::OVERLAPS.iter().any(|&o| size / o == hop));
+    if !valid {
+        return Err(format!(
+            "The hop must be a window size divided by 2, 4 or 8, got {}",
+            hop
+        ));
+    }
+    Ok(hop)
+}
+
+fn list_devices() {
+    println!("Hosts: {}", audio::list_hosts().join(", "));
+    let devices = audio::list_input_devices();
+    if devices.is_empty() {
+        println!("No input device found");
+    }
+    for device in devices {
+        println!("{}", device);
+    }
+}
+
+//Whether the option was typed or came from the config file, not a default : the gui only lets
+//these ones override the settings of its last session
+fn explicit(matches: &ArgMatches, ids: &[&str]) -> bool {
+    ids.iter()
+        .any(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
 }
 
 fn main() {
-    let args = Args::parse();
+    let command = Args::command();
+    let argv = match config::command_line(&command) {
+        Ok(argv) => argv,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let matches = command.get_matches_from(argv);
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if !dsp::window::OVERLAPS
+        .iter()
+        .any(|&overlap| args.window / overlap == args.hop())
+    {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!(
+                    "--hop {} does not divide --window {} by 2, 4 or 8",
+                    args.hop(),
+                    args.window
+                ),
+            )
+            .exit();
+    }
+    if args.list_devices {
+        list_devices();
+        return;
+    }
+    let tuning = match args.tuning() {
+        Ok(tuning) => tuning,
+        Err(e) => {
+            eprintln!("Failed to load the tuning: {}", e);
+            return;
+        }
+    };
+    if let Some(Command::Analyze(analyze)) = &args.command {
+        if let Err(e) = analyze::run(&args, analyze, tuning) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
     match args.ui {
         Ui::Gui => {
             let options = eframe::NativeOptions::default();
+            let input = explicit(
+                &matches,
+                &[
+                    "device",
+                    "channel",
+                    "input",
+                    "generate",
+                    "amplitude",
+                    "sample_rate",
+                    "fast",
+                ],
+            )
+            .then(|| args.input_source());
+            let visualizer = explicit(&matches, &["visualizer"]).then_some(args.visualizer);
+            let pitch = explicit(&matches, &["pitch"]).then_some(args.pitch);
+            let tuning =
+                explicit(&matches, &["temperament", "key", "scl", "kbm"]).then_some(tuning);
+            let instrument = explicit(&matches, &["instrument"]).then(|| args.instrument.clone());
+            let window =
+                explicit(&matches, &["window", "hop"]).then_some((args.window, args.hop()));
+            let ballistics = explicit(&matches, &["ballistics"]).then_some(args.ballistics);
+            let a4 = args.a4;
+            let record = args.record.clone();
             let _ = eframe::run_native(
                 "Tuner",
                 options,
-                Box::new(|_cc| Ok(Box::new(TunerApp::new(DeviceType::Desktop)))),
+                Box::new(move |cc| {
+                    let mut app = TunerApp::new(DeviceType::Desktop);
+                    app.restore(cc.storage);
+                    //the command line and the config file win over the saved settings
+                    let mut settings = app.settings();
+                    if let Some(input) = input {
+                        settings.input = input;
+                    }
+                    if let Some(visualizer) = visualizer {
+                        settings.visualizer = visualizer;
+                    }
+                    if let Some(pitch) = pitch {
+                        settings.pitch_algorithm = pitch;
+                    }
+                    if let Some(tuning) = tuning {
+                        settings.tuning = tuning;
+                    }
+                    if let Some(instrument) = instrument {
+                        settings.instrument = instrument;
+                    }
+                    if let Some((size, hop)) = window {
+                        settings.analysis_window = size;
+                        settings.analysis_hop = hop;
+                    }
+                    if let Some(a4) = a4 {
+                        settings.a4 = a4;
+                    }
+                    if let Some(ballistics) = ballistics {
+                        settings.ballistics = ballistics;
+                    }
+                    app.apply_settings(settings);
+                    //the capture starts right away to record from the launch
+                    if let Some(record) = record {
+                        app.record_path = record.display().to_string();
+                        app.start_audio();
+                        app.start_recording();
+                        if let Some(e) = &app.record_error {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    Ok(Box::new(app))
+                }),
             );
         }
-        Ui::Cli => {
-            let (bridge, producer) = AudioBridge::new();
-            let mut dsp = DigitalSignalProcessor::new(bridge.consumer);
-            let mut backend = match NativeAudioBackend::new(producer) {
-                Ok(backend) => backend,
-                Err(e) => {
-                    eprintln!("Failed to create audio backend: {}", e);
-                    return;
-                }
-            };
-
-            dsp.sample_rate = backend.sample_rate();
-
-            if let Err(e) = backend.start() {
-                eprintln!("Failed to start backend: {}", e);
-            }
-
-            loop {
-                std::thread::sleep(Duration::from_millis(8));
-                dsp.update(args.visualizer);
-                match args.visualizer {
-                    Visualizer::RMS => {
-                        let bars = (dsp.get_rms() * 100.0) as usize;
-                        println!("{: <50}", "█".repeat(bars));
-                    }
-                    Visualizer::WaveForm => {}
-                    Visualizer::Freq => {}
-                }
+        Ui::Cli => cli::run(&args, tuning),
+        Ui::Tui => {
+            let mut tui = tui::Tui::new(args.input_source());
+            tui.visualizer = args.visualizer;
+            tui.pitch = args.pitch;
+            tui.a4 = args.a4.unwrap_or(dsp::note::DEFAULT_A4);
+            tui.tuning = tuning;
+            tui.instrument = args.instrument.clone();
+            tui.window = (args.window, args.hop());
+            tui.ballistics = args.ballistics;
+            tui.record = args.record.clone();
+            if let Err(e) = tui.run() {
+                eprintln!("Terminal ui failed: {}", e);
             }
-        } // Ui::Tui => {
-          //
-          // }
+        }
     }
 }
 
@@ -0,0 +1,142 @@
+use clap::ValueEnum;
+use dsp::DigitalSignalProcessor;
+use dsp::metering::to_db;
+use std::io::{self, Write};
+
+///How the cli writes each analysis
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    ///Human readable, drawn live in the terminal unless written to a file
+    Plain,
+    ///One JSON object per line (JSON Lines)
+    Json,
+    ///Comma separated values, with a header line
+    Csv,
+}
+
+///What we know about the signal at the end of one analysis window
+pub struct Record {
+    ///Seconds of audio since the start
+    pub time: f64,
+    pub rms: f32,
+    ///Sample peak of the window, in dBFS
+    pub peak_db: f32,
+    pub frequency: Option<f32>,
+    pub note: Option<String>,
+    pub cents: Option<f32>,
+    pub clarity: Option<f32>,
+}
+
+impl Record {
+    pub fn from_dsp(dsp: &DigitalSignalProcessor) -> Self {
+        let note = dsp.get_note();
+        Self {
+            time: dsp.time(),
+            rms: dsp.get_rms(),
+            peak_db: to_db(dsp.get_peak()),
+            frequency: dsp.get_frequency(),
+            note: note.map(|n| n.to_string()),
+            cents: note.map(|n| n.cents),
+            clarity: dsp.get_clarity(),
+        }
+    }
+
+    ///Written once before the records, only csv has one
+    pub fn write_header(format: OutputFormat, out: &mut dyn Write) -> io::Result<()> {
+        match format {
+            OutputFormat::Csv => writeln!(out, "time,rms,peak_dbfs,frequency,note,cents,clarity"),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn write(&self, format: OutputFormat, out: &mut dyn Write) -> io::Result<()> {
+        match format {
+            OutputFormat::Plain => writeln!(
+                out,
+                "{:>9.3}s rms {:.4} peak {:>6.1} dBFS  {:<4} {:>8} Hz {:>6} cents clarity {}",
+                self.time,
+                self.rms,
+                self.peak_db,
+                self.note.as_deref().unwrap_or("--"),
+                optional(self.frequency, 2, "-"),
+                optional(self.cents, 1, "-"),
+                optional(self.clarity, 2, "-"),
+            ),
+            OutputFormat::Json => writeln!(
+                out,
+                "{{\"time\":{:.6},\"rms\":{:.6},\"peak_dbfs\":{:.2},\"frequency\":{},\"note\":{},\"cents\":{},\"clarity\":{}}}",
+                self.time,
+                self.rms,
+                self.peak_db,
+                optional(self.frequency, 3, "null"),
+                self.note
+                    .as_ref()
+                    .map_or("null".to_string(), |n| format!("\"{}\"", n)),
+                optional(self.cents, 2, "null"),
+                optional(self.clarity, 3, "null"),
+            ),
+            OutputFormat::Csv => writeln!(
+                out,
+                "{:.6},{:.6},{:.2},{},{},{},{}",
+                self.time,
+                self.rms,
+                self.peak_db,
+                optional(self.frequency, 3, ""),
+                self.note.as_deref().unwrap_or(""),
+                optional(self.cents, 2, ""),
+                optional(self.clarity, 3, ""),
+            ),
+        }
+    }
+}
+
+//A JSON string literal, quotes included
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+//A CSV field always quoted, its quotes doubled (RFC 4180)
+pub(crate) fn csv_field(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
+}
+
+pub(crate) fn optional(value: Option<f32>, precision: usize, missing: &str) -> String {
+    match value {
+        Some(v) => format!("{:.*}", precision, v),
+        None => missing.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_json_strings() {
+        assert_eq!(json_string("take.wav"), "\"take.wav\"");
+        assert_eq!(
+            json_string("a \"b\"\\c\n\u{1b}"),
+            "\"a \\\"b\\\"\\\\c\\n\\u001b\""
+        );
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("take.wav"), "\"take.wav\"");
+        assert_eq!(csv_field("a \"b\", c"), "\"a \"\"b\"\", c\"");
+    }
+}
@@ -0,0 +1,476 @@
+use crate::cli::{cents_meter, stop_recording, strings_line, waterfall_line};
+use audio::audio_bridge::{AudioBridge, BridgeStats};
+use audio::backend::AudioBackend;
+use audio::{InputSource, Recorder};
+use dsp::{
+    Analysis, Ballistics, DigitalSignalProcessor, DspWorker, InstrumentTuning, PitchAlgorithm,
+    PitchHistory, PitchPoint, TuningSystem, Visualizer,
+};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, Gauge, GraphType, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const VISUALIZERS: [Visualizer; 6] = [
+    Visualizer::RMS,
+    Visualizer::WaveForm,
+    Visualizer::Freq,
+    Visualizer::PitchHistory,
+    Visualizer::Spectrum,
+    Visualizer::Spectrogram,
+];
+
+//seconds shown by the pitch history
+const HISTORY_SECONDS: f32 = 10.0;
+
+//lower than this, the level meter is empty
+const METER_FLOOR_DB: f32 = -60.0;
+
+const HELP: &str = "tab/1-6 view  +/- A4 0.1 Hz  up/down A4 1 Hz  space start/stop  q quit";
+
+///The audio chain while capturing : it is rebuilt on each start, so a stopped tui holds no device
+struct Capture {
+    backend: Box<dyn AudioBackend>,
+    worker: DspWorker,
+    stats: BridgeStats,
+    recorder: Option<Recorder>,
+}
+
+///Terminal frontend : the same DSP as the gui, drawn with ratatui, usable over ssh
+pub struct Tui {
+    pub input: InputSource,
+    pub visualizer: Visualizer,
+    pub pitch: PitchAlgorithm,
+    pub a4: f32,
+    pub tuning: TuningSystem,
+    pub instrument: Option<InstrumentTuning>,
+    pub window: (usize, usize),
+    pub ballistics: Ballistics,
+    ///WAV file the first capture is recorded to
+    pub record: Option<PathBuf>,
+    capture: Option<Capture>,
+    analysis: Analysis,
+    history: PitchHistory,
+    status: String,
+}
+
+impl Tui {
+    pub fn new(input: InputSource) -> Self {
+        Self {
+            input,
+            visualizer: Visualizer::Freq,
+            pitch: PitchAlgorithm::Yin,
+            a4: dsp::note::DEFAULT_A4,
+            tuning: TuningSystem::default(),
+            instrument: None,
+            window: (4096, 1024),
+            ballistics: Ballistics::Digital,
+            record: None,
+            capture: None,
+            analysis: Analysis::default(),
+            history: PitchHistory::new(HISTORY_SECONDS),
+            status: String::new(),
+        }
+    }
+
+    ///Takes over the terminal until q, and gives it back as it was
+    pub fn run(mut self) -> Result<(), String> {
+        let mut terminal = ratatui::init();
+        self.start();
+        let result = self.event_loop(&mut terminal);
+        let report = self.stop();
+        ratatui::restore();
+        if let Some(report) = report {
+            eprintln!("{}", report);
+        }
+        result
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<(), String> {
+        loop {
+            if let Some(capture) = &mut self.capture {
+                capture.worker.set_visualizer(self.visualizer);
+                self.analysis = capture.worker.latest().clone();
+                self.history.push(&self.analysis);
+                if capture.backend.is_finished() {
+                    self.status = self.stop().unwrap_or_else(|| "End of input".to_string());
+                }
+            }
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(|e| format!("Failed to draw: {}", e))?;
+
+            //about 30 frames per second, a key press wakes us up sooner
+            if !event::poll(Duration::from_millis(33)).map_err(|e| e.to_string())? {
+                continue;
+            }
+            let Event::Key(key) = event::read().map_err(|e| e.to_string())? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(());
+                }
+                KeyCode::Char(' ') => {
+                    if self.capture.is_some() {
+                        self.status = self.stop().unwrap_or_else(|| "Stopped".to_string());
+                    } else {
+                        self.start();
+                    }
+                }
+                KeyCode::Tab => self.cycle_visualizer(1),
+                KeyCode::BackTab => self.cycle_visualizer(VISUALIZERS.len() - 1),
+                KeyCode::Char(c @ '1'..='6') => {
+                    self.visualizer = VISUALIZERS[c as usize - '1' as usize];
+                }
+                KeyCode::Char('+') | KeyCode::Char('=') => self.change_a4(0.1),
+                KeyCode::Char('-') => self.change_a4(-0.1),
+                KeyCode::Up => self.change_a4(1.0),
+                KeyCode::Down => self.change_a4(-1.0),
+                _ => {}
+            }
+        }
+    }
+
+    fn cycle_visualizer(&mut self, step: usize) {
+        let current = VISUALIZERS
+            .iter()
+            .position(|&v| v == self.visualizer)
+            .unwrap_or(0);
+        self.visualizer = VISUALIZERS[(current + step) % VISUALIZERS.len()];
+    }
+
+    fn change_a4(&mut self, delta: f32) {
+        let (min, max) = (*dsp::note::A4_RANGE.start(), *dsp::note::A4_RANGE.end());
+        self.a4 = (((self.a4 + delta) * 10.0).round() / 10.0).clamp(min, max);
+        if let Some(capture) = &self.capture {
+            let a4 = self.a4;
+            capture.worker.configure(move |dsp| dsp.set_a4(a4));
+        }
+    }
+
+    fn start(&mut self) {
+        if self.capture.is_some() {
+            return;
+        }
+        let (bridge, producer) = AudioBridge::new();
+        let stats = bridge.stats.clone();
+        let mut dsp = DigitalSignalProcessor::new(bridge.consumer);
+        dsp.set_live(self.input.is_live());
+        let mut backend = match self.input.open(producer) {
+            Ok(backend) => backend,
+            Err(e) => {
+                self.status = format!("Failed to create audio backend: {}", e);
+                return;
+            }
+        };
+        dsp.sample_rate = backend.sample_rate();
+        dsp.set_pitch_algorithm(self.pitch);
+        dsp.set_a4(self.a4);
+        dsp.set_tuning(self.tuning.clone());
+        dsp.set_instrument(self.instrument.clone());
+        dsp.set_window_size(self.window.0, self.window.1);
+        dsp.meter.set_ballistics(self.ballistics);
+        self.status = "Capturing".to_string();
+        //only the first capture is recorded, a restart does not overwrite the take
+        let recorder = match self.record.take() {
+            Some(path) => match Recorder::start(&bridge.tap, &path, backend.sample_rate()) {
+                Ok(recorder) => {
+                    self.status = format!("Recording to {}", path.display());
+                    Some(recorder)
+                }
+                Err(e) => {
+                    self.status = e;
+                    None
+                }
+            },
+            None => None,
+        };
+        if let Err(e) = backend.start() {
+            self.abandon(format!("Failed to start backend: {}", e), recorder);
+            return;
+        }
+        let worker = match DspWorker::spawn(dsp, self.visualizer) {
+            Ok(worker) => worker,
+            Err(e) => {
+                backend.stop();
+                self.abandon(e, recorder);
+                return;
+            }
+        };
+        self.capture = Some(Capture {
+            backend,
+            worker,
+            stats,
+            recorder,
+        });
+    }
+
+    //a capture that failed to start finishes its WAV file, and gives its path back for the next one
+    fn abandon(&mut self, status: String, recorder: Option<Recorder>) {
+        self.status = status;
+        if let Some(recorder) = recorder {
+            self.record = Some(recorder.path().to_path_buf());
+            if let Err(e) = recorder.stop() {
+                self.status = format!("{} ({})", self.status, e);
+            }
+        }
+    }
+
+    //returns what the recording became, if this capture was recorded
+    fn stop(&mut self) -> Option<String> {
+        //the backend stops feeding the ringbuff before the worker is joined
+        let recorder = self.capture.take().and_then(|mut capture| {
+            capture.backend.stop();
+            capture.recorder
+        });
+        self.analysis = Analysis::default();
+        self.history.clear();
+        recorder.map(stop_recording)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [header, meter, main, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(3),
+            Constraint::Min(5),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let state = if self.capture.is_some() {
+            "●".red()
+        } else {
+            "■".dark_gray()
+        };
+        frame.render_widget(
+            Line::from(vec![
+                state,
+                format!(" {}", self.visualizer).bold(),
+                format!("  A4 {:.1} Hz", self.a4).into(),
+                format!("  {}", self.status).dark_gray(),
+            ]),
+            header,
+        );
+        self.draw_meter(frame, meter);
+
+        let block = Block::bordered().title(format!(" {} ", self.visualizer));
+        let inner = block.inner(main);
+        frame.render_widget(block, main);
+        match self.visualizer {
+            Visualizer::RMS => self.draw_rms(frame, inner),
+            Visualizer::WaveForm => self.draw_waveform(frame, inner),
+            Visualizer::Freq => self.draw_tuner(frame, inner),
+            Visualizer::Spectrum => self.draw_spectrum(frame, inner),
+            Visualizer::Spectrogram => self.draw_spectrogram(frame, inner),
+            Visualizer::PitchHistory => self.draw_pitch_history(frame, inner),
+        }
+
+        let footer_text = match &self.capture {
+            Some(capture) => format!("{}  |  {}", HELP, capture.stats.snapshot()),
+            None => HELP.to_string(),
+        };
+        frame.render_widget(Line::from(footer_text).dark_gray(), footer);
+    }
+
+    //the bar follows the ballistics, the clip count stays in the title once something clipped
+    fn draw_meter(&self, frame: &mut Frame, area: Rect) {
+        let reading = &self.analysis.meter;
+        let db = reading.level_db;
+        let ratio = ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0);
+        let color = match db {
+            _ if db > -6.0 => Color::Red,
+            _ if db > -18.0 => Color::Yellow,
+            _ => Color::Green,
+        };
+        let mut title = vec![format!(" Level {} ", self.ballistics).into()];
+        if reading.clips > 0 {
+            title.push(format!(" CLIP {} ", reading.clips).white().on_red());
+        }
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(Line::from(title)))
+                .gauge_style(Style::new().fg(color))
+                .ratio(ratio as f64)
+                .label(format!(
+                    "{:.1} dBFS  hold {:.1}  true peak {:.1}",
+                    db, reading.hold_db, reading.true_peak_db
+                )),
+            area,
+        );
+    }
+
+    fn draw_rms(&self, frame: &mut Frame, area: Rect) {
+        let reading = &self.analysis.meter;
+        let lines = vec![
+            Line::from(format!("Peak       {:>6.1} dBFS", reading.peak_db)),
+            Line::from(format!("True peak  {:>6.1} dBTP", reading.true_peak_db)),
+            Line::from(format!("RMS        {:>6.1} dBFS", reading.rms_db)),
+            Line::from(format!("Hold       {:>6.1} dBFS", reading.hold_db)),
+            Line::from(format!(
+                "{:<10} {:>6.1} dBFS",
+                self.ballistics, reading.level_db
+            )),
+            Line::from(format!("Clips      {:>6}", reading.clips)),
+        ];
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+
+    fn draw_waveform(&self, frame: &mut Frame, area: Rect) {
+        //braille packs two points per cell horizontally. The line goes down and up each column
+        //of the envelope, which fills it
+        let envelope = self.analysis.get_envelope(area.width as usize * 2);
+        let points: Vec<(f64, f64)> = envelope
+            .iter()
+            .enumerate()
+            .flat_map(|(i, e)| [(i as f64, e.max as f64), (i as f64, e.min as f64)])
+            .collect();
+        let dataset = Dataset::default()
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::new().fg(Color::Cyan))
+            .data(&points);
+        let chart = Chart::new(vec![dataset])
+            .x_axis(Axis::default().bounds([0.0, envelope.len().max(1) as f64]))
+            .y_axis(Axis::default().bounds([-1.0, 1.0]));
+        frame.render_widget(chart, area);
+    }
+
+    fn draw_tuner(&self, frame: &mut Frame, area: Rect) {
+        let (Some(note), Some(freq)) = (self.analysis.note, self.analysis.frequency) else {
+            frame.render_widget(Paragraph::new("Listening...").dark_gray(), area);
+            return;
+        };
+        let color = if note.in_tune() {
+            Color::Green
+        } else {
+            Color::Yellow
+        };
+        let width = (area.width as usize).clamp(3, 81);
+        let mut lines = vec![
+            Line::from(vec![
+                Span::from(note.to_string()).bold().fg(color),
+                format!("  {:+.1} cents", note.cents).into(),
+            ]),
+            Line::from(format!(
+                "{:.2} Hz, target {:.2} Hz",
+                freq, note.target_frequency
+            )),
+            Line::from(""),
+            Line::from(cents_meter(note.cents, width)).fg(color),
+            Line::from(format!("{:<w$}{:>w$}", "-50", "+50", w = width / 2)).dark_gray(),
+            Line::from(""),
+            Line::from(format!(
+                "{} clarity {:.2}",
+                self.analysis.pitch_algorithm,
+                self.analysis.clarity.unwrap_or(0.0)
+            ))
+            .dark_gray(),
+        ];
+        let strings = strings_line(self.analysis.strings.as_ref());
+        if !strings.is_empty() {
+            lines.push(Line::from(strings.trim_start().to_string()));
+        }
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+
+    fn draw_spectrum(&self, frame: &mut Frame, area: Rect) {
+        //the same log axis as the waterfall, each column is the level of its bin above -100 dB
+        let bins = self.analysis.get_spectrum();
+        if bins.len() < 2 || area.width < 2 {
+            return;
+        }
+        let width = area.width as usize;
+        let nyquist = self.analysis.sample_rate / 2.0;
+        let bin_width = nyquist / (bins.len() - 1) as f32;
+        let data: Vec<u64> = (0..width)
+            .map(|column| {
+                let t = column as f32 / (width - 1) as f32;
+                let freq = dsp::FrequencyAxis::Log.frequency(t, 20.0, nyquist);
+                let bin = ((freq / bin_width).round() as usize).min(bins.len() - 1);
+                (bins[bin] + 100.0).clamp(0.0, 100.0) as u64
+            })
+            .collect();
+        frame.render_widget(
+            Sparkline::default()
+                .data(&data)
+                .max(100)
+                .style(Style::new().fg(Color::Magenta)),
+            area,
+        );
+    }
+
+    fn draw_pitch_history(&self, frame: &mut Frame, area: Rect) {
+        //one line per held note, so unvoiced analyses and note changes leave a gap
+        let latest = self.history.latest();
+        let mut runs: Vec<Vec<(f64, f64)>> = Vec::new();
+        let mut previous = None;
+        for &PitchPoint { time, note } in self.history.points() {
+            let Some(note) = note else {
+                previous = None;
+                continue;
+            };
+            let point = (time - latest, note.cents as f64);
+            match runs.last_mut() {
+                Some(run) if previous == Some(note.midi) => run.push(point),
+                _ => runs.push(vec![point]),
+            }
+            previous = Some(note.midi);
+        }
+        let datasets = runs
+            .iter()
+            .map(|run| {
+                Dataset::default()
+                    .marker(Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(Style::new().fg(Color::Yellow))
+                    .data(run)
+            })
+            .collect();
+        let note = self
+            .analysis
+            .note
+            .map_or("--".to_string(), |n| n.to_string());
+        let chart = Chart::new(datasets)
+            .x_axis(
+                Axis::default()
+                    .bounds([-HISTORY_SECONDS as f64, 0.0])
+                    .labels([format!("-{}s", HISTORY_SECONDS), "now".to_string()]),
+            )
+            .y_axis(
+                Axis::default()
+                    .title(note)
+                    .bounds([-50.0, 50.0])
+                    .labels(["-50", "0", "+50"]),
+            );
+        frame.render_widget(chart, area);
+    }
+
+    fn draw_spectrogram(&self, frame: &mut Frame, area: Rect) {
+        //newest line on top
+        let lines: Vec<Line> = self
+            .analysis
+            .spectrogram
+            .iter()
+            .rev()
+            .take(area.height as usize)
+            .map(|bins| {
+                Line::from(waterfall_line(
+                    bins,
+                    self.analysis.sample_rate,
+                    area.width as usize,
+                ))
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+}
@@ -22,7 +22,11 @@ pub async fn start() -> Result<(), JsValue> {
         .start(
             canvas,
             eframe::WebOptions::default(),
-            Box::new(|_cc| Ok(Box::new(TunerApp::new(get_ui_type(window))) as Box<dyn App>)),
+            Box::new(|cc| {
+                let mut app = TunerApp::new(get_ui_type(window));
+                app.restore(cc.storage);
+                Ok(Box::new(app) as Box<dyn App>)
+            }),
         )
         .await
 }